@@ -0,0 +1,132 @@
+// Command line subcommands, for working with puzzle files without opening the window
+
+use std::path::Path;
+
+use sudoku_solver::{export, formats, photo, raster};
+
+const USAGE: &str = "usage:
+    sudoku_solver [puzzle file]
+    sudoku_solver export <puzzle file> <output .svg, .pdf or .tex> [--per-page N] [--solutions]
+    sudoku_solver render <puzzle file> <output .png> [--size PIXELS] [--index N] [--solution] [--candidates]
+    sudoku_solver import <photo .png or .jpg> <output puzzle file>
+    sudoku_solver convert <puzzle file> <output puzzle file> [--rules TEXT]";
+
+// Runs the subcommand named by the first argument, returning the exit code, or None if the GUI should open instead
+pub fn run(args: &[String]) -> Option<i32> {
+    let result = match args.first().map(|a| a.as_str()) {
+        Some("export") => export_command(&args[1..]),
+        Some("render") => render_command(&args[1..]),
+        Some("import") => import_command(&args[1..]),
+        Some("convert") => convert_command(&args[1..]),
+        Some("help") | Some("--help") | Some("-h") => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => return None,
+    };
+    match result {
+        Ok(()) => Some(0),
+        Err(e) => {
+            eprintln!("{}", e);
+            Some(1)
+        }
+    }
+}
+
+fn export_command(args: &[String]) -> Result<(), String> {
+    let mut paths: Vec<&String> = Vec::new();
+    let mut options = export::PrintOptions::default();
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--solutions" => options.include_solutions = true,
+            "--per-page" => {
+                i += 1;
+                options.puzzles_per_page = args.get(i).and_then(|n| n.parse().ok()).filter(|n| *n >= 1)
+                    .ok_or("--per-page needs a number of puzzles of at least 1")?;
+            }
+            _ => paths.push(&args[i]),
+        }
+        i += 1;
+    }
+    match paths[..] {
+        [input, output] => {
+            let puzzles = formats::load_file(Path::new(input))?;
+            export::save_file(Path::new(output), &puzzles, &options)
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn render_command(args: &[String]) -> Result<(), String> {
+    let mut paths: Vec<&String> = Vec::new();
+    let mut options = raster::RenderOptions::default();
+    let mut index = 1; // which puzzle of the file to draw, counting from 1
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--solution" => options.show_solution = true,
+            "--candidates" => options.show_candidates = true,
+            "--size" => {
+                i += 1;
+                options.size = args.get(i).and_then(|n| n.parse().ok()).filter(|n| *n >= 16)
+                    .ok_or("--size needs a number of pixels of at least 16")?;
+            }
+            "--index" => {
+                i += 1;
+                index = args.get(i).and_then(|n| n.parse().ok()).filter(|n| *n >= 1)
+                    .ok_or("--index needs a puzzle number of at least 1")?;
+            }
+            _ => paths.push(&args[i]),
+        }
+        i += 1;
+    }
+    match paths[..] {
+        [input, output] => {
+            let puzzles = formats::load_file(Path::new(input))?;
+            let puzzle = puzzles.get(index - 1).ok_or_else(|| format!("{} only has {} puzzles", input, puzzles.len()))?;
+            raster::save_png(Path::new(output), puzzle, &options)
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn import_command(args: &[String]) -> Result<(), String> {
+    match args {
+        [input, output] => {
+            let puzzle = photo::import_file(Path::new(input))?;
+            formats::save_file(Path::new(output), &[puzzle])
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+// Rewrites puzzles in another format, like a .variant file for sharing, optionally giving them a rules text (where
+// a \n starts a new line)
+fn convert_command(args: &[String]) -> Result<(), String> {
+    let mut paths: Vec<&String> = Vec::new();
+    let mut rules: Option<String> = None;
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--rules" => {
+                i += 1;
+                rules = Some(args.get(i).ok_or("--rules needs the text of the rules")?.replace("\\n", "\n"));
+            }
+            _ => paths.push(&args[i]),
+        }
+        i += 1;
+    }
+    match paths[..] {
+        [input, output] => {
+            let mut puzzles = formats::load_file(Path::new(input))?;
+            if let Some(rules) = rules {
+                for puzzle in puzzles.iter_mut() {
+                    puzzle.metadata.rules = Some(rules.clone());
+                }
+            }
+            formats::save_file(Path::new(output), &puzzles)
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
@@ -0,0 +1,679 @@
+// Printable output of puzzles as SVG, PDF or LaTeX
+//
+// Puzzles are first laid out onto A4 pages as lines, shaded squares and text, measured in points from the top left corner,
+// and the pages are then written out in whichever format was asked for. Thick lines are drawn wherever two
+// neighbouring squares are in different regions, so boxes of any shape and jigsaw regions are drawn the same way.
+// LaTeX documents leave the page layout to LaTeX, and only use the same drawing for each board as a TikZ picture.
+
+use std::fs;
+use std::path::Path;
+
+use crate::puzzle::{Constraint, EdgeMarker, LineKind, OutsideClue, Puzzle};
+use crate::rules;
+
+const PAGE_WIDTH: f64 = 595.28;
+const PAGE_HEIGHT: f64 = 841.89;
+const MARGIN: f64 = 36.0;
+const TITLE_SIZE: f64 = 14.0;
+const THIN_LINE: f64 = 0.5;
+const THICK_LINE: f64 = 2.0;
+
+pub const GIVEN_COLOR: (u8, u8, u8) = (0, 0, 0);
+pub const SOLVED_COLOR: (u8, u8, u8) = (0, 70, 200);
+pub const CANDIDATE_COLOR: (u8, u8, u8) = (110, 110, 110);
+pub const SHADE_COLOR: (u8, u8, u8) = (220, 220, 220);
+pub const LINE_COLOR: (u8, u8, u8) = (0, 0, 0);
+pub const THERMOMETER_COLOR: (u8, u8, u8) = (200, 200, 200);
+pub const ARROW_COLOR: (u8, u8, u8) = (90, 90, 90);
+pub const WHISPER_COLOR: (u8, u8, u8) = (110, 200, 110);
+pub const RENBAN_COLOR: (u8, u8, u8) = (210, 160, 230);
+pub const PALINDROME_COLOR: (u8, u8, u8) = (170, 170, 170);
+const ARROW_LINE: f64 = 1.0;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrintOptions {
+    pub puzzles_per_page: usize,
+    pub include_solutions: bool, // solutions are printed after all of the puzzles, starting on a new page
+}
+
+impl Default for PrintOptions {
+    fn default() -> PrintOptions {
+        PrintOptions { puzzles_per_page: 1, include_solutions: false }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shape {
+    Line { from: (f64, f64), to: (f64, f64), width: f64, color: (u8, u8, u8) },
+    Text { at: (f64, f64), size: f64, bold: bool, color: (u8, u8, u8), text: String }, // centered horizontally and vertically on `at`
+    Rect { from: (f64, f64), to: (f64, f64), color: (u8, u8, u8) }, // filled in, from the top left corner to the bottom right
+    Circle { at: (f64, f64), radius: f64, color: (u8, u8, u8) }, // filled in
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Page {
+    pub shapes: Vec<Shape>,
+}
+
+// Draws a board with its top left corner at (x, y), showing `values` with the givens in bold and everything else in
+// another color. Empty squares show their `candidates` (if any) in a small grid, each value in its own spot.
+pub fn draw_board(shapes: &mut Vec<Shape>, puzzle: &Puzzle, values: &[Vec<Option<i32>>], candidates: Option<&[Vec<Vec<i32>>]>, x: f64, y: f64, size: f64) {
+    let side_length = puzzle.side_length;
+    let n = side_length as usize;
+    // clues outside the grid get a margin of one square all around it, out of the space the board has
+    let (x, y, size) = if puzzle.constraints.iter().any(|constraint| constraint.outside_clue().is_some()) {
+        let margin = size / (side_length + 2) as f64;
+        (x + margin, y + margin, size - 2.0 * margin)
+    } else {
+        (x, y, size)
+    };
+    let cell = size / side_length as f64;
+    let (box_rows, box_cols) = puzzle.box_shape;
+    let regions = puzzle.region_map();
+    let box_num = |r: usize, c: usize| regions[r][c];
+
+    // shading goes first, so that the lines and text are drawn on top of it
+    if puzzle.constraints.contains(&Constraint::Windows) {
+        for (r, c) in rules::window_units(side_length, puzzle.box_shape).into_iter().flatten() {
+            let (left, top) = (x + c as f64 * cell, y + r as f64 * cell);
+            shapes.push(Shape::Rect { from: (left, top), to: (left + cell, top + cell), color: SHADE_COLOR });
+        }
+    }
+    // even squares are shaded as a square, odd ones as a circle
+    for constraint in &puzzle.constraints {
+        if let Constraint::Parity { cell: (r, c), even } = constraint {
+            let (left, top) = (x + *c as f64 * cell, y + *r as f64 * cell);
+            if *even {
+                shapes.push(Shape::Rect { from: (left + cell * 0.12, top + cell * 0.12), to: (left + cell * 0.88, top + cell * 0.88), color: SHADE_COLOR });
+            } else {
+                shapes.push(Shape::Circle { at: (left + cell / 2.0, top + cell / 2.0), radius: cell * 0.4, color: SHADE_COLOR });
+            }
+        }
+    }
+    for constraint in &puzzle.constraints {
+        if let Constraint::Thermometer { cells } = constraint {
+            let center = |&(r, c): &(usize, usize)| (x + (c as f64 + 0.5) * cell, y + (r as f64 + 0.5) * cell);
+            for pair in cells.windows(2) {
+                shapes.push(Shape::Line { from: center(&pair[0]), to: center(&pair[1]), width: cell * 0.25, color: THERMOMETER_COLOR });
+            }
+            if let Some(bulb) = cells.first() {
+                shapes.push(Shape::Circle { at: center(bulb), radius: cell * 0.38, color: THERMOMETER_COLOR });
+            }
+        }
+    }
+    for constraint in &puzzle.constraints {
+        if let Constraint::Line { kind, cells } = constraint {
+            let center = |&(r, c): &(usize, usize)| (x + (c as f64 + 0.5) * cell, y + (r as f64 + 0.5) * cell);
+            for pair in cells.windows(2) {
+                shapes.push(Shape::Line { from: center(&pair[0]), to: center(&pair[1]), width: cell * 0.15, color: line_color(*kind) });
+            }
+        }
+    }
+    for constraint in &puzzle.constraints {
+        if let Constraint::Arrow { circle, cells } = constraint {
+            for (a, b) in arrow_lines(circle, cells) {
+                shapes.push(Shape::Line { from: (x + a.0 * cell, y + a.1 * cell), to: (x + b.0 * cell, y + b.1 * cell), width: ARROW_LINE, color: ARROW_COLOR });
+            }
+        }
+    }
+    if puzzle.constraints.contains(&Constraint::Diagonals) {
+        shapes.push(Shape::Line { from: (x, y), to: (x + size, y + size), width: THIN_LINE, color: LINE_COLOR });
+        shapes.push(Shape::Line { from: (x + size, y), to: (x, y + size), width: THIN_LINE, color: LINE_COLOR });
+    }
+    for constraint in &puzzle.constraints {
+        if let Constraint::Cage { cells, sum } = constraint {
+            // a dashed outline just inside the squares, with the sum in the corner of the top left square
+            for (from, to) in cage_outline(cells, 0.1) {
+                for (a, b) in dashes(from, to, 0.08) {
+                    shapes.push(Shape::Line { from: (x + a.0 * cell, y + a.1 * cell), to: (x + b.0 * cell, y + b.1 * cell), width: THIN_LINE, color: LINE_COLOR });
+                }
+            }
+            if let Some(&(r, c)) = cells.iter().min() {
+                shapes.push(Shape::Text {
+                    at: (x + (c as f64 + 0.27) * cell, y + (r as f64 + 0.27) * cell),
+                    size: cell * 0.22,
+                    bold: false,
+                    color: GIVEN_COLOR,
+                    text: sum.to_string(),
+                });
+            }
+        }
+    }
+    for constraint in &puzzle.constraints {
+        if let Some(clue) = constraint.outside_clue() {
+            let at = outside_clue_text(&clue);
+            shapes.push(Shape::Text { at: (x + at.0 * cell, y + at.1 * cell), size: cell * 0.4, bold: false, color: GIVEN_COLOR, text: clue.sum.to_string() });
+            for (a, b) in outside_clue_arrow(&clue) {
+                shapes.push(Shape::Line { from: (x + a.0 * cell, y + a.1 * cell), to: (x + b.0 * cell, y + b.1 * cell), width: THIN_LINE, color: LINE_COLOR });
+            }
+        }
+    }
+    // the outside border, then the right and bottom edge of every square that is not on the border
+    shapes.push(Shape::Line { from: (x, y), to: (x + size, y), width: THICK_LINE, color: LINE_COLOR });
+    shapes.push(Shape::Line { from: (x, y + size), to: (x + size, y + size), width: THICK_LINE, color: LINE_COLOR });
+    shapes.push(Shape::Line { from: (x, y), to: (x, y + size), width: THICK_LINE, color: LINE_COLOR });
+    shapes.push(Shape::Line { from: (x + size, y), to: (x + size, y + size), width: THICK_LINE, color: LINE_COLOR });
+    for (r, row) in values.iter().enumerate() {
+        for (c, value) in row.iter().enumerate() {
+            let (left, top) = (x + c as f64 * cell, y + r as f64 * cell);
+            if c + 1 < n {
+                let width = if box_num(r, c) != box_num(r, c + 1) {THICK_LINE} else {THIN_LINE};
+                shapes.push(Shape::Line { from: (left + cell, top), to: (left + cell, top + cell), width, color: LINE_COLOR });
+            }
+            if r + 1 < n {
+                let width = if box_num(r, c) != box_num(r + 1, c) {THICK_LINE} else {THIN_LINE};
+                shapes.push(Shape::Line { from: (left, top + cell), to: (left + cell, top + cell), width, color: LINE_COLOR });
+            }
+            if let Some(v) = *value {
+                let given = puzzle.givens[r][c].is_some();
+                let text = puzzle.symbols.symbol(v);
+                shapes.push(Shape::Text {
+                    at: (left + cell / 2.0, top + cell / 2.0),
+                    size: cell * 0.6 * symbol_scale(&text),
+                    bold: given,
+                    color: if given {GIVEN_COLOR} else {SOLVED_COLOR},
+                    text,
+                });
+            } else if let Some(candidates) = candidates {
+                // laid out like the squares of a box, so a 6x6 puzzle's candidates are in 2 rows of 3
+                let mini = cell / box_rows.max(box_cols) as f64;
+                let (across, down) = (box_cols as f64 * mini, box_rows as f64 * mini);
+                for v in &candidates[r][c] {
+                    let (mini_row, mini_col) = ((v - 1) / box_cols, (v - 1) % box_cols);
+                    let text = puzzle.symbols.symbol(*v);
+                    shapes.push(Shape::Text {
+                        at: (left + (cell - across) / 2.0 + (mini_col as f64 + 0.5) * mini, top + (cell - down) / 2.0 + (mini_row as f64 + 0.5) * mini),
+                        size: mini * 0.8 * symbol_scale(&text),
+                        bold: false,
+                        color: CANDIDATE_COLOR,
+                        text,
+                    });
+                }
+            }
+        }
+    }
+    // markers go on top of the grid lines they sit on
+    for constraint in &puzzle.constraints {
+        if let Constraint::Edge { marker, cells } = constraint {
+            let (mx, my) = edge_middle(*cells);
+            let at = (x + mx * cell, y + my * cell);
+            match marker {
+                EdgeMarker::WhiteDot | EdgeMarker::BlackDot => {
+                    shapes.push(Shape::Circle { at, radius: cell * 0.12, color: LINE_COLOR });
+                    if *marker == EdgeMarker::WhiteDot {
+                        shapes.push(Shape::Circle { at, radius: cell * 0.12 - THICK_LINE / 2.0, color: (255, 255, 255) });
+                    }
+                }
+                EdgeMarker::X | EdgeMarker::V => {
+                    let half = cell * 0.13;
+                    shapes.push(Shape::Rect { from: (at.0 - half, at.1 - half), to: (at.0 + half, at.1 + half), color: (255, 255, 255) });
+                    let text = if *marker == EdgeMarker::X {"X"} else {"V"};
+                    shapes.push(Shape::Text { at, size: cell * 0.3, bold: true, color: LINE_COLOR, text: text.to_string() });
+                }
+                EdgeMarker::Less => {
+                    for (a, b) in inequality_lines(*cells) {
+                        shapes.push(Shape::Line { from: (x + a.0 * cell, y + a.1 * cell), to: (x + b.0 * cell, y + b.1 * cell), width: THICK_LINE, color: LINE_COLOR });
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn line_color(kind: LineKind) -> (u8, u8, u8) {
+    match kind {
+        LineKind::Whisper => WHISPER_COLOR,
+        LineKind::Renban => RENBAN_COLOR,
+        LineKind::Palindrome => PALINDROME_COLOR,
+    }
+}
+
+// The middle of the edge between two squares next to each other, in squares from the top left corner of the board
+pub fn edge_middle([(r1, c1), (r2, c2)]: [(usize, usize); 2]) -> (f64, f64) {
+    ((c1 + c2) as f64 / 2.0 + 0.5, (r1 + r2) as f64 / 2.0 + 0.5)
+}
+
+// The two strokes of a < (or ^ between squares one above the other) on the edge between two squares, opening towards
+// the second square, the one with the larger value. Measured in squares from the top left corner of the board.
+pub fn inequality_lines(cells: [(usize, usize); 2]) -> Vec<((f64, f64), (f64, f64))> {
+    let (mx, my) = edge_middle(cells);
+    let [(r1, c1), (r2, c2)] = cells;
+    // towards the larger square, and across the edge
+    let (dx, dy) = (c2 as f64 - c1 as f64, r2 as f64 - r1 as f64);
+    let (ax, ay) = (-dy, dx);
+    let tip = (mx - dx * 0.07, my - dy * 0.07);
+    let end = |side: f64| (mx + dx * 0.07 + ax * side * 0.12, my + dy * 0.07 + ay * side * 0.12);
+    vec![(tip, end(1.0)), (tip, end(-1.0))]
+}
+
+// Where the number of a clue outside the grid goes, in squares from the top left corner of the board (so it can be
+// negative). A little killer's number moves back from the corner its arrow points out of.
+pub fn outside_clue_text(clue: &OutsideClue) -> (f64, f64) {
+    let (dx, dy) = if clue.step.0 != 0 && clue.step.1 != 0 {(clue.step.1 as f64, clue.step.0 as f64)} else {(0.0, 0.0)};
+    (clue.at.1 as f64 + 0.5 - dx * 0.12, clue.at.0 as f64 + 0.5 - dy * 0.12)
+}
+
+// The lines of the small arrow pointing along a little killer's diagonal, from the square of its clue. Sandwich clues
+// don't have one.
+pub fn outside_clue_arrow(clue: &OutsideClue) -> Vec<((f64, f64), (f64, f64))> {
+    let center = (clue.at.1 as f64 + 0.5, clue.at.0 as f64 + 0.5);
+    let (dx, dy) = (clue.step.1 as f64, clue.step.0 as f64);
+    if dx == 0.0 || dy == 0.0 {
+        return Vec::new();
+    }
+    let tip = (center.0 + dx * 0.42, center.1 + dy * 0.42);
+    let tail = (center.0 + dx * 0.2, center.1 + dy * 0.2);
+    let head = 0.1;
+    vec![(tail, tip), (tip, (tip.0 - dx * head, tip.1)), (tip, (tip.0, tip.1 - dy * head))]
+}
+
+// The outline of a cage drawn `inset` squares inside its edges, as lines between (x, y) points measured in squares
+// from the top left corner of the board. Inner corners reach across into the neighbouring squares of the cage, so the
+// lines always join up.
+pub fn cage_outline(cells: &[(usize, usize)], inset: f64) -> Vec<((f64, f64), (f64, f64))> {
+    let has = |r: i64, c: i64| r >= 0 && c >= 0 && cells.contains(&(r as usize, c as usize));
+    // how far past a square's edge a line goes: stopping short if the cage ends there, through an inner corner
+    let overhang = |along: bool, across: bool| if !along {-inset} else if across {inset} else {0.0};
+    let mut lines = Vec::new();
+    for &(row, col) in cells {
+        let (r, c) = (row as i64, col as i64);
+        let (left, top, right, bottom) = (col as f64, row as f64, col as f64 + 1.0, row as f64 + 1.0);
+        if !has(r - 1, c) {
+            let y = top + inset;
+            lines.push(((left - overhang(has(r, c - 1), has(r - 1, c - 1)), y), (right + overhang(has(r, c + 1), has(r - 1, c + 1)), y)));
+        }
+        if !has(r + 1, c) {
+            let y = bottom - inset;
+            lines.push(((left - overhang(has(r, c - 1), has(r + 1, c - 1)), y), (right + overhang(has(r, c + 1), has(r + 1, c + 1)), y)));
+        }
+        if !has(r, c - 1) {
+            let x = left + inset;
+            lines.push(((x, top - overhang(has(r - 1, c), has(r - 1, c - 1))), (x, bottom + overhang(has(r + 1, c), has(r + 1, c - 1)))));
+        }
+        if !has(r, c + 1) {
+            let x = right - inset;
+            lines.push(((x, top - overhang(has(r - 1, c), has(r - 1, c + 1))), (x, bottom + overhang(has(r + 1, c), has(r + 1, c + 1)))));
+        }
+    }
+    lines
+}
+
+// An arrow as lines between (x, y) points measured in squares from the top left corner of the board: the outline of
+// its circle (stretched into a pill from the first square to the last, if it has more than one), then the shaft from
+// the edge of the circle through the middle of each square, ending in an arrowhead
+pub fn arrow_lines(circle: &[(usize, usize)], cells: &[(usize, usize)]) -> Vec<((f64, f64), (f64, f64))> {
+    let radius = 0.4;
+    let center = |&(r, c): &(usize, usize)| (c as f64 + 0.5, r as f64 + 0.5);
+    let unit = |from: (f64, f64), to: (f64, f64)| {
+        let length = ((to.0 - from.0).powi(2) + (to.1 - from.1).powi(2)).sqrt();
+        if length == 0.0 {(1.0, 0.0)} else {((to.0 - from.0) / length, (to.1 - from.1) / length)}
+    };
+    let mut lines = Vec::new();
+    let (first, last) = match (circle.iter().min(), circle.iter().max()) {
+        (Some(first), Some(last)) => (center(first), center(last)),
+        _ => return lines,
+    };
+    // half a circle around each end of the pill, which join up into its outline
+    let direction = unit(first, last);
+    let base = direction.1.atan2(direction.0);
+    let steps = 12;
+    let mut outline = Vec::new();
+    for (end, start_angle) in [(last, base - std::f64::consts::FRAC_PI_2), (first, base + std::f64::consts::FRAC_PI_2)] {
+        for i in 0..=steps {
+            let angle = start_angle + std::f64::consts::PI * i as f64 / steps as f64;
+            outline.push((end.0 + radius * angle.cos(), end.1 + radius * angle.sin()));
+        }
+    }
+    for i in 0..outline.len() {
+        lines.push((outline[i], outline[(i + 1) % outline.len()]));
+    }
+
+    let mut points: Vec<(f64, f64)> = cells.iter().map(center).collect();
+    let Some(&tip) = points.last() else {
+        return lines;
+    };
+    // the shaft leaves the circle from the square nearest to it
+    let distance = |a: (f64, f64), b: (f64, f64)| (a.0 - b.0).powi(2) + (a.1 - b.1).powi(2);
+    let nearest = circle.iter().map(center).min_by(|a, b| distance(*a, points[0]).total_cmp(&distance(*b, points[0]))).unwrap_or(first);
+    let out = unit(nearest, points[0]);
+    points.insert(0, (nearest.0 + radius * out.0, nearest.1 + radius * out.1));
+    for pair in points.windows(2) {
+        lines.push((pair[0], pair[1]));
+    }
+    let (back, across) = (unit(tip, points[points.len() - 2]), 0.2);
+    let barb = |side: f64| (tip.0 + 0.25 * back.0 - side * across * back.1, tip.1 + 0.25 * back.1 + side * across * back.0);
+    lines.push((tip, barb(1.0)));
+    lines.push((tip, barb(-1.0)));
+    lines
+}
+
+// Splits a line into dashes of about `dash` long, with gaps of the same length between them
+pub fn dashes(from: (f64, f64), to: (f64, f64), dash: f64) -> Vec<((f64, f64), (f64, f64))> {
+    let length = ((to.0 - from.0).powi(2) + (to.1 - from.1).powi(2)).sqrt();
+    let count = ((length / dash / 2.0).round() as usize).max(1);
+    let step = 1.0 / (2 * count) as f64;
+    let point = |t: f64| (from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t);
+    (0..count).map(|i| (point((2 * i) as f64 * step + step / 2.0), point((2 * i + 1) as f64 * step + step / 2.0))).collect()
+}
+
+// Shrinks symbols of more than two characters (like the 100 of a 100x100 puzzle) so that they still fit their square
+fn symbol_scale(text: &str) -> f64 {
+    (2.0 / text.chars().count() as f64).min(1.0)
+}
+
+// Splits the printable area of a page into evenly sized slots, one per puzzle
+fn slots(puzzles_per_page: usize) -> Vec<(f64, f64, f64, f64)> {
+    let cols = if puzzles_per_page <= 2 {1} else {2};
+    let rows = puzzles_per_page.div_ceil(cols);
+    let slot_width = (PAGE_WIDTH - 2.0 * MARGIN) / cols as f64;
+    let slot_height = (PAGE_HEIGHT - 2.0 * MARGIN) / rows as f64;
+    (0..puzzles_per_page).map(|i| (MARGIN + (i % cols) as f64 * slot_width, MARGIN + (i / cols) as f64 * slot_height, slot_width, slot_height)).collect()
+}
+
+// A board waiting to be placed on a page, along with the title printed above it
+struct TitledBoard<'a> {
+    title: String,
+    puzzle: &'a Puzzle,
+    values: Vec<Vec<Option<i32>>>,
+}
+
+fn place_boards(pages: &mut Vec<Page>, boards: &[TitledBoard], puzzles_per_page: usize) {
+    let slots = slots(puzzles_per_page);
+    for chunk in boards.chunks(puzzles_per_page) {
+        let mut page = Page::default();
+        for (TitledBoard { title, puzzle, values }, (x, y, w, h)) in chunk.iter().zip(slots.iter()) {
+            let size = (w - TITLE_SIZE).min(h - 3.0 * TITLE_SIZE) * 0.95;
+            let left = x + (w - size) / 2.0;
+            let top = y + 2.0 * TITLE_SIZE;
+            page.shapes.push(Shape::Text { at: (x + w / 2.0, y + TITLE_SIZE), size: TITLE_SIZE, bold: true, color: GIVEN_COLOR, text: title.clone() });
+            draw_board(&mut page.shapes, puzzle, values, None, left, top, size);
+        }
+        pages.push(page);
+    }
+}
+
+// Gives every puzzle its title, showing either just the givens or the whole solution
+fn titled_boards(puzzles: &[Puzzle], solutions: bool) -> Vec<TitledBoard<'_>> {
+    puzzles.iter().enumerate().map(|(i, p)| {
+        let title = p.metadata.title.clone().unwrap_or_else(|| format!("Puzzle {}", i + 1));
+        if solutions {
+            let result = p.solve();
+            let heading = if result.solved {"Solution"} else {"No solution"};
+            TitledBoard { title: format!("{}: {}", heading, title), puzzle: p, values: result.values }
+        } else {
+            TitledBoard { title, puzzle: p, values: p.givens.clone() }
+        }
+    }).collect()
+}
+
+// Lays out every puzzle (and, if asked for, every solution) onto pages
+pub fn layout(puzzles: &[Puzzle], options: &PrintOptions) -> Vec<Page> {
+    let per_page = options.puzzles_per_page.max(1);
+    let mut pages = Vec::new();
+    place_boards(&mut pages, &titled_boards(puzzles, false), per_page);
+    if options.include_solutions {
+        place_boards(&mut pages, &titled_boards(puzzles, true), per_page);
+    }
+    pages
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// Writes all pages into a single SVG, one below the other
+pub fn to_svg(pages: &[Page]) -> String {
+    let height = PAGE_HEIGHT * pages.len() as f64;
+    let mut result = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}pt\" height=\"{h}pt\" viewBox=\"0 0 {w} {h}\">\n<rect width=\"{w}\" height=\"{h}\" fill=\"white\"/>\n",
+        w = PAGE_WIDTH, h = height
+    );
+    for (i, page) in pages.iter().enumerate() {
+        result.push_str(&format!("<g transform=\"translate(0 {})\">\n", PAGE_HEIGHT * i as f64));
+        for shape in &page.shapes {
+            match shape {
+                Shape::Line { from, to, width, color } => result.push_str(&format!(
+                    "<line x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\" stroke=\"rgb({},{},{})\" stroke-width=\"{:.2}\" stroke-linecap=\"square\"/>\n",
+                    from.0, from.1, to.0, to.1, color.0, color.1, color.2, width
+                )),
+                Shape::Text { at, size, bold, color, text } => result.push_str(&format!(
+                    "<text x=\"{:.2}\" y=\"{:.2}\" font-family=\"Helvetica, Arial, sans-serif\" font-size=\"{:.2}\" font-weight=\"{}\" fill=\"rgb({},{},{})\" text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>\n",
+                    at.0, at.1, size, if *bold {"bold"} else {"normal"}, color.0, color.1, color.2, escape_xml(text)
+                )),
+                Shape::Rect { from, to, color } => result.push_str(&format!(
+                    "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" fill=\"rgb({},{},{})\"/>\n",
+                    from.0, from.1, to.0 - from.0, to.1 - from.1, color.0, color.1, color.2
+                )),
+                Shape::Circle { at, radius, color } => result.push_str(&format!(
+                    "<circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"{:.2}\" fill=\"rgb({},{},{})\"/>\n",
+                    at.0, at.1, radius, color.0, color.1, color.2
+                )),
+            }
+        }
+        result.push_str("</g>\n");
+    }
+    result.push_str("</svg>\n");
+    result
+}
+
+// The standard PDF fonts only cover latin text, so anything else is replaced
+fn escape_pdf(text: &str) -> String {
+    text.chars().map(|ch| match ch {
+        '\\' | '(' | ')' => format!("\\{}", ch),
+        ' '..='~' => ch.to_string(),
+        _ => "?".to_string(),
+    }).collect()
+}
+
+// Writes the pages as a PDF using the built in Helvetica fonts, so no fonts need to be embedded
+pub fn to_pdf(pages: &[Page]) -> Vec<u8> {
+    // objects 1 to 4 are the catalog, the page tree and the two fonts, then each page is followed by its contents
+    let mut objects: Vec<String> = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        format!("<< /Type /Pages /Kids [{}] /Count {} >>", (0..pages.len()).map(|i| format!("{} 0 R", 5 + 2 * i)).collect::<Vec<_>>().join(" "), pages.len()),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>".to_string(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>".to_string(),
+    ];
+    for (i, page) in pages.iter().enumerate() {
+        let mut content = String::new();
+        for shape in &page.shapes {
+            match shape {
+                Shape::Line { from, to, width, color } => content.push_str(&format!(
+                    "{:.3} {:.3} {:.3} RG {:.2} w 2 J {:.2} {:.2} m {:.2} {:.2} l S\n",
+                    color.0 as f64 / 255.0, color.1 as f64 / 255.0, color.2 as f64 / 255.0,
+                    width, from.0, PAGE_HEIGHT - from.1, to.0, PAGE_HEIGHT - to.1
+                )),
+                Shape::Text { at, size, bold, color, text } => {
+                    // Helvetica is close enough to 0.55em per character to center digits and short titles
+                    let width = 0.55 * size * text.chars().count() as f64;
+                    content.push_str(&format!(
+                        "{:.3} {:.3} {:.3} rg BT /{} {:.2} Tf {:.2} {:.2} Td ({}) Tj ET\n",
+                        color.0 as f64 / 255.0, color.1 as f64 / 255.0, color.2 as f64 / 255.0,
+                        if *bold {"F2"} else {"F1"}, size, at.0 - width / 2.0, PAGE_HEIGHT - at.1 - 0.35 * size, escape_pdf(text)
+                    ));
+                }
+                Shape::Rect { from, to, color } => content.push_str(&format!(
+                    "{:.3} {:.3} {:.3} rg {:.2} {:.2} {:.2} {:.2} re f\n",
+                    color.0 as f64 / 255.0, color.1 as f64 / 255.0, color.2 as f64 / 255.0,
+                    from.0, PAGE_HEIGHT - to.1, to.0 - from.0, to.1 - from.1
+                )),
+                Shape::Circle { at, radius, color } => {
+                    // four Bezier curves, one per quarter, with the usual 0.5523 control point distance
+                    let (cx, cy, k) = (at.0, PAGE_HEIGHT - at.1, radius * 0.5523);
+                    content.push_str(&format!(
+                        "{:.3} {:.3} {:.3} rg {:.2} {:.2} m {:.2} {:.2} {:.2} {:.2} {:.2} {:.2} c {:.2} {:.2} {:.2} {:.2} {:.2} {:.2} c {:.2} {:.2} {:.2} {:.2} {:.2} {:.2} c {:.2} {:.2} {:.2} {:.2} {:.2} {:.2} c f\n",
+                        color.0 as f64 / 255.0, color.1 as f64 / 255.0, color.2 as f64 / 255.0,
+                        cx + radius, cy,
+                        cx + radius, cy + k, cx + k, cy + radius, cx, cy + radius,
+                        cx - k, cy + radius, cx - radius, cy + k, cx - radius, cy,
+                        cx - radius, cy - k, cx - k, cy - radius, cx, cy - radius,
+                        cx + k, cy - radius, cx + radius, cy - k, cx + radius, cy
+                    ));
+                }
+            }
+        }
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+            PAGE_WIDTH, PAGE_HEIGHT, 6 + 2 * i
+        ));
+        objects.push(format!("<< /Length {} >>\nstream\n{}endstream", content.len(), content));
+    }
+
+    let mut result = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::new();
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(result.len());
+        result.push_str(&format!("{} 0 obj\n{}\nendobj\n", i + 1, object));
+    }
+    let xref_start = result.len();
+    result.push_str(&format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1));
+    for offset in offsets {
+        result.push_str(&format!("{:010} 00000 n \n", offset));
+    }
+    result.push_str(&format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref_start));
+    result.into_bytes()
+}
+
+fn escape_latex(text: &str) -> String {
+    text.chars().map(|ch| match ch {
+        '\\' => "\\textbackslash{}".to_string(),
+        '~' => "\\textasciitilde{}".to_string(),
+        '^' => "\\textasciicircum{}".to_string(),
+        '{' | '}' | '$' | '&' | '#' | '_' | '%' => format!("\\{}", ch),
+        _ => ch.to_string(),
+    }).collect()
+}
+
+// Draws a board as a TikZ picture with squares `cell_cm` wide, measuring everything in points like the other formats
+fn tikz_board(board: &TitledBoard, cell_cm: f64) -> String {
+    let mut shapes = Vec::new();
+    let size = board.puzzle.side_length as f64 * cell_cm * 72.27 / 2.54;
+    draw_board(&mut shapes, board.puzzle, &board.values, None, 0.0, 0.0, size);
+    let mut result = String::from("\\begin{tikzpicture}[x=1pt, y=-1pt]\n");
+    for shape in shapes {
+        match shape {
+            Shape::Line { from, to, width, color } => result.push_str(&format!(
+                "  \\draw[draw={{rgb,255:red,{};green,{};blue,{}}}, line width={:.2}pt, line cap=rect] ({:.2},{:.2}) -- ({:.2},{:.2});\n",
+                color.0, color.1, color.2, width, from.0, from.1, to.0, to.1
+            )),
+            Shape::Text { at, size, bold, color, text } => result.push_str(&format!(
+                "  \\node[text={{rgb,255:red,{};green,{};blue,{}}}, font=\\sffamily{}\\fontsize{{{:.1}}}{{{:.1}}}\\selectfont] at ({:.2},{:.2}) {{{}}};\n",
+                color.0, color.1, color.2, if bold {"\\bfseries"} else {""}, size, size * 1.2, at.0, at.1, escape_latex(&text)
+            )),
+            Shape::Rect { from, to, color } => result.push_str(&format!(
+                "  \\fill[fill={{rgb,255:red,{};green,{};blue,{}}}] ({:.2},{:.2}) rectangle ({:.2},{:.2});\n",
+                color.0, color.1, color.2, from.0, from.1, to.0, to.1
+            )),
+            Shape::Circle { at, radius, color } => result.push_str(&format!(
+                "  \\fill[fill={{rgb,255:red,{};green,{};blue,{}}}] ({:.2},{:.2}) circle ({:.2});\n",
+                color.0, color.1, color.2, at.0, at.1, radius
+            )),
+        }
+    }
+    result.push_str("\\end{tikzpicture}\n");
+    result
+}
+
+fn latex_boards(result: &mut String, boards: &[TitledBoard], puzzles_per_page: usize) {
+    // one puzzle fills the page width, two are stacked, and more go in two columns
+    let (columns, width_cm) = match puzzles_per_page {
+        1 => (1, 15.0),
+        2 => (1, 10.0),
+        _ => (2, 7.5),
+    };
+    for (page, chunk) in boards.chunks(puzzles_per_page).enumerate() {
+        if page > 0 || !result.ends_with("\\begin{document}\n") {
+            result.push_str("\\newpage\n");
+        }
+        for (i, board) in chunk.iter().enumerate() {
+            let cell_cm = width_cm / board.puzzle.side_length as f64;
+            result.push_str(&format!("\\begin{{minipage}}{{{}\\linewidth}}\n\\centering\n", if columns == 1 {"1.0"} else {"0.48"}));
+            result.push_str(&format!("\\textbf{{\\large {}}}\\\\[0.5em]\n", escape_latex(&board.title)));
+            result.push_str(&tikz_board(board, cell_cm));
+            result.push_str("\\end{minipage}\n");
+            let row_done = columns == 1 || i % columns == columns - 1;
+            result.push_str(if row_done {"\n\\vspace{1cm}\n\n"} else {"\\hfill\n"});
+        }
+    }
+}
+
+// Writes a whole LaTeX document with TikZ drawings of the puzzles, ready to go into a puzzle book
+pub fn to_latex(puzzles: &[Puzzle], options: &PrintOptions) -> String {
+    let per_page = options.puzzles_per_page.max(1);
+    let mut result = String::from(
+        "\\documentclass[a4paper]{article}\n\\usepackage[margin=2cm]{geometry}\n\\usepackage{tikz}\n\\pagestyle{empty}\n\\setlength{\\parindent}{0pt}\n\n\\begin{document}\n"
+    );
+    latex_boards(&mut result, &titled_boards(puzzles, false), per_page);
+    if options.include_solutions {
+        latex_boards(&mut result, &titled_boards(puzzles, true), per_page);
+    }
+    result.push_str("\\end{document}\n");
+    result
+}
+
+// Writes puzzles to an .svg, .pdf or .tex file, depending on its extension
+pub fn save_file(path: &Path, puzzles: &[Puzzle], options: &PrintOptions) -> Result<(), String> {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    let contents = match ext.as_str() {
+        "svg" => to_svg(&layout(puzzles, options)).into_bytes(),
+        "pdf" => to_pdf(&layout(puzzles, options)),
+        "tex" => to_latex(puzzles, options).into_bytes(),
+        _ => return Err(format!("Can only export to .svg, .pdf or .tex, not {}", path.display())),
+    };
+    fs::write(path, contents).map_err(|e| format!("Could not write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats;
+
+    // A 4x4 puzzle with one of most kinds of constraint, so every kind of shape gets drawn
+    fn variant() -> Puzzle {
+        formats::parse_variant(
+            "size 4\nbox 2x2\nrule diagonals\ncage 3 r1c1 r1c2\nthermo r2c1 r2c2\nedge less r3c1 r3c2\nedge white r4c3 r4c4\n\
+             arrow r1c4 -> r1c3\nline renban r3c3 r3c4\nparity even r4c1\ntitle Fish & <Chips>\ngivens 1...........2...\n"
+        ).unwrap()
+    }
+
+    #[test]
+    fn svg_export() {
+        let options = PrintOptions { puzzles_per_page: 1, include_solutions: true };
+        let svg = to_svg(&layout(&[variant()], &options));
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\"") && svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<g transform").count(), 2); // the puzzle's page and the solution's
+        for element in ["<line ", "<text ", "<rect ", "<circle "] {
+            assert!(svg.contains(element), "no {} in the SVG", element);
+        }
+        assert!(svg.contains("Fish &amp; &lt;Chips&gt;"));
+    }
+
+    #[test]
+    fn pdf_export() {
+        let options = PrintOptions { puzzles_per_page: 1, include_solutions: true };
+        let pdf = String::from_utf8(to_pdf(&layout(&[variant()], &options))).unwrap();
+        assert!(pdf.starts_with("%PDF-1.4\n") && pdf.ends_with("%%EOF\n"));
+        assert!(pdf.contains("/Count 2"));
+        // startxref has to point at the cross-reference table
+        let xref: usize = pdf.rsplit("startxref\n").next().unwrap().lines().next().unwrap().parse().unwrap();
+        assert!(pdf[xref..].starts_with("xref\n"));
+        let lengths: Vec<usize> = pdf.split("/Length ").skip(1).map(|rest| rest.split(' ').next().unwrap().parse().unwrap()).collect();
+        assert!(lengths.len() == 2 && lengths.iter().all(|len| *len > 0), "a page was left empty");
+    }
+
+    #[test]
+    fn latex_export() {
+        let options = PrintOptions { puzzles_per_page: 2, include_solutions: true };
+        let latex = to_latex(&[variant(), variant(), variant()], &options);
+        assert!(latex.starts_with("\\documentclass[a4paper]{article}\n") && latex.ends_with("\\end{document}\n"));
+        assert!(latex.contains("\\usepackage{tikz}") && latex.contains("\\begin{document}\n"));
+        // two pages of puzzles, then the solutions start on a new page
+        assert_eq!(latex.matches("\\newpage").count(), 3);
+        assert_eq!(latex.matches("\\begin{tikzpicture}").count(), 6);
+        assert_eq!(latex.matches("\\end{tikzpicture}").count(), 6);
+        for command in ["\\draw[", "\\node[", ") rectangle (", ") circle ("] {
+            assert!(latex.contains(command), "no {} in the LaTeX", command);
+        }
+        assert!(latex.contains("Fish \\& <Chips>"));
+    }
+}
@@ -0,0 +1,1068 @@
+// Readers and writers for the puzzle file formats used by other Sudoku programs
+//
+// Supported formats:
+//  - SadMan Software `.sdk`: optional `#` metadata lines followed by one line per row, `.` or `0` for blanks.
+//    Newer files split the grid into a `[Puzzle]` section (the givens) and a `[State]` section (the current entries).
+//  - Simple Sudoku `.ss`: like `.sdk`, but rows may contain `|` box separators and `---!---!---` separator lines.
+//  - `.sdm`: many puzzles in one file, each written as a single line of side_length * side_length characters.
+//  - HoDoKu library lines: `:0000:x:<puzzle>:<deleted candidates>::`, where a `+` in front of a digit marks a
+//    placed (non-given) value and the deleted candidates are space separated `<digit><row><col>` triples.
+//  - Pencil mark grids as exported by HoDoKu and SudoCue: every cell is written as the list of its candidates,
+//    with a single digit meaning the cell is given. This program also writes a `+` in front of a digit that was
+//    entered rather than given, a `.` in front of a single candidate, and a lone `.` for a cell with no candidates.
+//  - `.sudoku` save games: this program's own format, one `key value` line per field of puzzle::Puzzle, so that a
+//    game keeps its givens, entries, pencil marks, elapsed time, jigsaw regions, extra rules (like `rule diagonals`,
+//    `cage 15 r1c1 r1c2 r2c1` for a killer cage, `thermo r1c1 r2c2` from the bulb up, `arrow r1c1 -> r2c2 r3c3`
+//    from the circle along the shaft, `edge white r1c1 r1c2` for a Kropki dot or XV marker, `edge less r1c1 r1c2`
+//    for r1c1 < r1c2, `line whisper r1c1 r2c2 r3c2` for a whisper, renban or palindrome line, `parity odd r1c1` for
+//    an odd or even square, or `sandwich 12 r1c1 r1c2 ...` and `littlekiller 15 r1c2 r2c3 ...` for a clue outside
+//    the grid, its squares listed going away from it) and the symbols it is shown with.
+//  - `.variant` puzzle files: the save game format without the player's progress (no entries, pencil marks or
+//    elapsed time, which are left out when writing and an error when reading), for sharing variant puzzles. Every
+//    field is described above parse_save_game.
+//  - JSON (with the `serde` feature): the whole puzzle::Puzzle document, or a list of them.
+//  - `.multi` files: overlapping grids (a multigrid::MultiPuzzle), as a `layout samurai` line naming how the grids
+//    are put together and a `givens` line with every square of the canvas. They aren't a puzzle::Puzzle, so they
+//    are read and written by load_multigrid_file and save_multigrid_file rather than load_file and save_file.
+//
+// Every format but JSON writes one character per cell, using the 61 symbols 1-9, then A-Z, then a-z (see
+// symbols::SymbolSet). The largest of those sizes that can be split into boxes is 60, so files hold puzzles of up to
+// 60x60, though the GUI only opens puzzles of up to 49x49 (its MAX_SIDE_LENGTH). Only save games and JSON keep
+// jigsaw regions and extra rules, the other formats are for plain sudoku.
+
+use std::fs;
+use std::path::Path;
+
+use crate::multigrid::{self, Layout, MultiPuzzle};
+use crate::puzzle::{Constraint, EdgeMarker, LineKind, Metadata, Puzzle};
+use crate::solver;
+use crate::symbols::SymbolSet;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Sdk,
+    SimpleSudoku,
+    Sdm,
+    HoDoKu,
+    PencilMarkGrid,
+    SaveGame,
+    Variant,
+    #[cfg(feature = "serde")]
+    Json,
+}
+
+impl Format {
+    pub fn from_extension(path: &Path) -> Option<Format> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "sdk" => Some(Format::Sdk),
+            "ss" => Some(Format::SimpleSudoku),
+            "sdm" => Some(Format::Sdm),
+            "hdk" => Some(Format::HoDoKu),
+            "pm" => Some(Format::PencilMarkGrid),
+            "sudoku" => Some(Format::SaveGame),
+            "variant" => Some(Format::Variant),
+            #[cfg(feature = "serde")]
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+
+    // Guesses the format of a file from its contents, for files with an unknown extension (like .txt)
+    pub fn detect(contents: &str) -> Format {
+        let lines: Vec<&str> = contents.lines().map(|l| l.trim()).filter(|l| !l.is_empty()).collect();
+        #[cfg(feature = "serde")]
+        if lines.first().is_some_and(|l| l.starts_with('{') || l.starts_with('[')) {
+            return Format::Json;
+        }
+        if lines.first().is_some_and(|l| l.starts_with("size ")) {
+            // only a save game says how far the player has got
+            let progress = lines.iter().any(|l| ["elapsed", "entries", "candidates"].contains(&l.split(' ').next().unwrap_or("")));
+            return if progress {Format::SaveGame} else {Format::Variant};
+        }
+        if lines.iter().any(|l| l.starts_with(':') && l.chars().any(|ch| ch.is_ascii_alphanumeric())) {
+            Format::HoDoKu
+        } else if lines.iter().any(|l| l.contains('|') && l.contains(char::is_whitespace) && l.split(|ch: char| ch == '|' || ch.is_whitespace()).any(|t| t.len() > 1)) {
+            Format::PencilMarkGrid
+        } else if lines.iter().any(|l| l.contains('|') || l.contains('!')) {
+            Format::SimpleSudoku
+        } else if is_sdm(&lines) {
+            Format::Sdm
+        } else {
+            Format::Sdk
+        }
+    }
+}
+
+// Reads every puzzle in a file, choosing the format from the extension or, failing that, the contents
+pub fn load_file(path: &Path) -> Result<Vec<Puzzle>, String> {
+    if is_multigrid_file(path) {
+        return Err(format!("{} holds overlapping grids rather than a single puzzle", path.display()));
+    }
+    let contents = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+    let format = Format::from_extension(path).unwrap_or_else(|| Format::detect(&contents));
+    parse(&contents, format)
+}
+
+// Writes puzzles to a file in the format given by its extension (pencil mark grid if the extension is unknown)
+pub fn save_file(path: &Path, puzzles: &[Puzzle]) -> Result<(), String> {
+    if is_multigrid_file(path) {
+        return Err("Only overlapping grids can be saved as .multi files".to_string());
+    }
+    let format = Format::from_extension(path).unwrap_or(Format::PencilMarkGrid);
+    let contents = write(puzzles, format)?;
+    fs::write(path, contents).map_err(|e| format!("Could not write {}: {}", path.display(), e))
+}
+
+pub fn parse(contents: &str, format: Format) -> Result<Vec<Puzzle>, String> {
+    match format {
+        Format::Sdk => parse_sdk(contents).map(|p| vec![p]),
+        Format::SimpleSudoku => parse_ss(contents).map(|p| vec![p]),
+        Format::Sdm => parse_sdm(contents),
+        Format::HoDoKu => parse_hodoku(contents),
+        Format::PencilMarkGrid => parse_pm_grid(contents).map(|p| vec![p]),
+        Format::SaveGame => parse_save_game(contents).map(|p| vec![p]),
+        Format::Variant => parse_variant(contents).map(|p| vec![p]),
+        #[cfg(feature = "serde")]
+        Format::Json => parse_json(contents),
+    }
+}
+
+// Formats that only hold one puzzle per file return an error when given more than one
+pub fn write(puzzles: &[Puzzle], format: Format) -> Result<String, String> {
+    let single = || match puzzles {
+        [p] => Ok(p),
+        _ => Err(format!("{:?} files hold exactly one puzzle, but {} were given", format, puzzles.len())),
+    };
+    match format {
+        Format::Sdk => single().map(write_sdk),
+        Format::SimpleSudoku => single().map(write_ss),
+        Format::Sdm => Ok(write_sdm(puzzles)),
+        Format::HoDoKu => Ok(write_hodoku(puzzles)),
+        Format::PencilMarkGrid => single().map(write_pm_grid),
+        Format::SaveGame => single().map(write_save_game),
+        Format::Variant => single().map(write_variant),
+        #[cfg(feature = "serde")]
+        Format::Json => write_json(puzzles),
+    }
+}
+
+// Whether the lines are whole puzzles, rather than the rows of one. A .sdk grid has as many rows as each row has
+// characters (twice as many with a [State] section), which a list of puzzles almost never does.
+fn is_sdm(lines: &[&str]) -> bool {
+    let grid: Vec<&str> = lines.iter().filter(|l| !l.starts_with('#') && !l.starts_with('[')).cloned().collect();
+    let width = grid.first().map_or(0, |l| l.chars().count());
+    grid.len() <= 1 || grid.iter().any(|l| l.chars().count() != width) || !grid.len().is_multiple_of(width)
+}
+
+// Works out the box shape from where the first row is split by `|`, as long as that gives boxes that fit the puzzle
+fn box_shape_from_separators(puzzle: &mut Puzzle, box_cols: Option<usize>) {
+    if let Some(box_cols) = box_cols.map(|c| c as i32) {
+        if box_cols > 0 && puzzle.side_length % box_cols == 0 {
+            puzzle.box_shape = (puzzle.side_length / box_cols, box_cols);
+        }
+    }
+}
+
+// Converts a single character into a cell value. The text formats all hold one character per cell, so they always
+// use the alphanumeric symbols, whatever symbols the puzzle is shown with.
+fn symbol_value(ch: char, side_length: i32) -> Result<Option<i32>, String> {
+    if ch == '.' || ch == '0' || ch == '-' || ch == '*' || ch == '_' {
+        return Ok(None);
+    }
+    match SymbolSet::Alphanumeric.value(&ch.to_string(), side_length) {
+        Some(v) => Ok(Some(v)),
+        None => Err(format!("'{}' is not a valid value for a {}x{} puzzle", ch, side_length, side_length)),
+    }
+}
+
+pub fn value_symbol(value: i32) -> char {
+    SymbolSet::Alphanumeric.symbol(value).chars().next().unwrap()
+}
+
+fn check_side_length(side_length: i32) -> Result<(), String> {
+    if side_length > 1 && solver::default_box_shape(side_length).is_some() && SymbolSet::Alphanumeric.supports(side_length) {
+        Ok(())
+    } else {
+        Err(format!("A side length of {} is not a supported puzzle size", side_length))
+    }
+}
+
+// Reads a grid given as one string per row
+fn parse_rows(rows: &[String]) -> Result<Vec<Vec<Option<i32>>>, String> {
+    let side_length = rows.len() as i32;
+    check_side_length(side_length)?;
+    let mut values = Vec::new();
+    for row in rows {
+        let chars: Vec<char> = row.chars().collect();
+        if chars.len() != side_length as usize {
+            return Err(format!("Expected {} cells in the row \"{}\"", side_length, row));
+        }
+        values.push(chars.into_iter().map(|ch| symbol_value(ch, side_length)).collect::<Result<Vec<_>, _>>()?);
+    }
+    Ok(values)
+}
+
+// Reads a grid given as a single line
+fn parse_line(line: &str) -> Result<Vec<Vec<Option<i32>>>, String> {
+    let chars: Vec<char> = line.chars().collect();
+    let side_length = (chars.len() as f64).sqrt().round() as i32;
+    if (side_length * side_length) as usize != chars.len() {
+        return Err(format!("A puzzle line must have a square number of cells, but \"{}\" has {}", line, chars.len()));
+    }
+    let rows: Vec<String> = chars.chunks(side_length as usize).map(|c| c.iter().collect()).collect();
+    parse_rows(&rows)
+}
+
+// Writes a grid one row per line, with separators between the boxes if their shape is given
+fn write_rows(values: &[Vec<Option<i32>>], separators: Option<(i32, i32)>) -> String {
+    let mut result = String::new();
+    for (r, row) in values.iter().enumerate() {
+        if let Some((box_rows, box_cols)) = separators {
+            if r > 0 && r % box_rows as usize == 0 {
+                let dashes = vec!["-".repeat(box_cols as usize); row.len() / box_cols as usize];
+                result.push_str(&dashes.join("!"));
+                result.push('\n');
+            }
+        }
+        for (c, val) in row.iter().enumerate() {
+            if separators.is_some_and(|(_, box_cols)| c > 0 && c % box_cols as usize == 0) {
+                result.push('|');
+            }
+            result.push(match val {
+                Some(v) => value_symbol(*v),
+                None => '.',
+            });
+        }
+        result.push('\n');
+    }
+    result
+}
+
+// Entries are only kept if they differ from the givens, so a [State] section that repeats the givens adds nothing
+fn entries_from_state(givens: &[Vec<Option<i32>>], state: &[Vec<Option<i32>>]) -> Vec<Vec<Option<i32>>> {
+    givens.iter().zip(state.iter())
+        .map(|(g_row, s_row)| g_row.iter().zip(s_row.iter()).map(|(g, s)| if g.is_none() { *s } else { None }).collect())
+        .collect()
+}
+
+pub fn parse_sdk(contents: &str) -> Result<Puzzle, String> {
+    let mut puzzle_rows: Vec<String> = Vec::new();
+    let mut state_rows: Vec<String> = Vec::new();
+    let mut in_state = false;
+    let mut metadata = Metadata::default();
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        } else if let Some(header) = line.strip_prefix('#') {
+            let mut chars = header.chars();
+            let text = Some(chars.as_str().get(1..).unwrap_or("").trim().to_string());
+            match chars.next() {
+                Some('A') => metadata.author = text,
+                Some('D') => metadata.title = text,
+                Some('C') => metadata.comment = text,
+                Some('S') => metadata.source = text,
+                Some('L') => metadata.difficulty = text,
+                _ => {} // dates, URLs and anything else are not kept
+            }
+        } else if line.eq_ignore_ascii_case("[puzzle]") {
+            in_state = false;
+        } else if line.eq_ignore_ascii_case("[state]") {
+            in_state = true;
+        } else if line.starts_with('[') {
+            return Err(format!("Unknown section {}", line));
+        } else if in_state {
+            state_rows.push(line.to_string());
+        } else {
+            puzzle_rows.push(line.to_string());
+        }
+    }
+    let givens = parse_rows(&puzzle_rows)?;
+    let side_length = givens.len() as i32;
+    let mut puzzle = Puzzle::new(side_length);
+    if !state_rows.is_empty() {
+        let state = parse_rows(&state_rows)?;
+        if state.len() != givens.len() {
+            return Err("The [State] grid is not the same size as the [Puzzle] grid".to_string());
+        }
+        puzzle.entries = entries_from_state(&givens, &state);
+    }
+    puzzle.givens = givens;
+    puzzle.metadata = metadata;
+    Ok(puzzle)
+}
+
+pub fn write_sdk(puzzle: &Puzzle) -> String {
+    let mut result = String::new();
+    let headers = [
+        ('A', &puzzle.metadata.author),
+        ('D', &puzzle.metadata.title),
+        ('C', &puzzle.metadata.comment),
+        ('S', &puzzle.metadata.source),
+        ('L', &puzzle.metadata.difficulty),
+    ];
+    for (code, text) in headers {
+        if let Some(text) = text {
+            result.push_str(&format!("#{}{}\n", code, text));
+        }
+    }
+
+    let has_entries = puzzle.entries.iter().flatten().any(|e| e.is_some());
+    if !has_entries {
+        result.push_str(&write_rows(&puzzle.givens, None));
+        return result;
+    }
+    let state: Vec<Vec<Option<i32>>> = (0..puzzle.side_length as usize)
+        .map(|r| (0..puzzle.side_length as usize).map(|c| puzzle.value(r, c)).collect())
+        .collect();
+    result.push_str(&format!("[Puzzle]\n{}[State]\n{}", write_rows(&puzzle.givens, None), write_rows(&state, None)));
+    result
+}
+
+pub fn parse_ss(contents: &str) -> Result<Puzzle, String> {
+    let mut rows: Vec<String> = Vec::new();
+    let mut box_cols: Option<usize> = None;
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.chars().all(|ch| ch == '-' || ch == '!' || ch == '+' || ch.is_whitespace()) {
+            continue;
+        }
+        if box_cols.is_none() && line.contains('|') {
+            box_cols = line.trim_start_matches('|').split('|').next().map(|box_row| box_row.chars().filter(|ch| !ch.is_whitespace()).count());
+        }
+        rows.push(line.chars().filter(|ch| *ch != '|' && !ch.is_whitespace()).collect());
+    }
+    let givens = parse_rows(&rows)?;
+    let mut puzzle = Puzzle::new(givens.len() as i32);
+    puzzle.givens = givens;
+    box_shape_from_separators(&mut puzzle, box_cols);
+    Ok(puzzle)
+}
+
+pub fn write_ss(puzzle: &Puzzle) -> String {
+    write_rows(&puzzle.givens, Some(puzzle.box_shape))
+}
+
+pub fn parse_sdm(contents: &str) -> Result<Vec<Puzzle>, String> {
+    let mut puzzles = Vec::new();
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let givens = parse_line(line)?;
+        let mut puzzle = Puzzle::new(givens.len() as i32);
+        puzzle.givens = givens;
+        puzzles.push(puzzle);
+    }
+    if puzzles.is_empty() {
+        return Err("The file does not contain any puzzles".to_string());
+    }
+    Ok(puzzles)
+}
+
+pub fn write_sdm(puzzles: &[Puzzle]) -> String {
+    puzzles.iter().map(|p| write_rows(&p.givens, None).replace('\n', "") + "\n").collect()
+}
+
+pub fn parse_hodoku(contents: &str) -> Result<Vec<Puzzle>, String> {
+    let mut puzzles = Vec::new();
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(':').collect();
+        if fields.len() < 5 || !fields[0].is_empty() {
+            return Err(format!("\"{}\" is not a HoDoKu library line", line));
+        }
+
+        // a '+' in front of a digit marks it as placed rather than given
+        let mut cells: Vec<(char, bool)> = Vec::new();
+        let mut placed = false;
+        for ch in fields[3].chars() {
+            if ch == '+' {
+                placed = true;
+            } else {
+                cells.push((ch, placed));
+                placed = false;
+            }
+        }
+        let values = parse_line(&cells.iter().map(|(ch, _)| *ch).collect::<String>())?;
+        let side_length = values.len() as i32;
+        let mut puzzle = Puzzle::new(side_length);
+        for (i, (_, placed)) in cells.iter().enumerate() {
+            let (r, c) = (i / side_length as usize, i % side_length as usize);
+            if *placed {
+                puzzle.entries[r][c] = values[r][c];
+            } else {
+                puzzle.givens[r][c] = values[r][c];
+            }
+        }
+
+        let deletions = fields[4].split_whitespace().collect::<Vec<&str>>();
+        if !deletions.is_empty() {
+            let mut candidates = puzzle.rule_candidates().ok_or_else(|| format!("The puzzle \"{}\" breaks the rules", fields[3]))?;
+            for deletion in deletions {
+                let parts = deletion.chars().map(|ch| symbol_value(ch, side_length)).collect::<Result<Vec<_>, _>>()?;
+                match parts[..] {
+                    [Some(v), Some(r), Some(c)] => candidates[(r - 1) as usize][(c - 1) as usize].retain(|x| *x != v),
+                    _ => return Err(format!("\"{}\" is not a valid deleted candidate", deletion)),
+                }
+            }
+            puzzle.candidates = candidates;
+        }
+        puzzles.push(puzzle);
+    }
+    if puzzles.is_empty() {
+        return Err("The file does not contain any puzzles".to_string());
+    }
+    Ok(puzzles)
+}
+
+pub fn write_hodoku(puzzles: &[Puzzle]) -> String {
+    let mut result = String::new();
+    for puzzle in puzzles {
+        let side_length = puzzle.side_length;
+        let mut grid = String::new();
+        for r in 0..side_length as usize {
+            for c in 0..side_length as usize {
+                match (puzzle.givens[r][c], puzzle.entries[r][c]) {
+                    (Some(v), _) => grid.push(value_symbol(v)),
+                    (None, Some(v)) => {
+                        grid.push('+');
+                        grid.push(value_symbol(v));
+                    }
+                    (None, None) => grid.push('.'),
+                }
+            }
+        }
+        let mut deletions: Vec<String> = Vec::new();
+        if let Some(allowed) = puzzle.rule_candidates() {
+            for (r, allowed_row) in allowed.iter().enumerate() {
+                for (c, allowed_vals) in allowed_row.iter().enumerate() {
+                    if puzzle.candidates[r][c].is_empty() {
+                        continue; // no pencil marks means nothing has been ruled out yet
+                    }
+                    for v in allowed_vals {
+                        if !puzzle.candidates[r][c].contains(v) {
+                            deletions.push([*v, r as i32 + 1, c as i32 + 1].iter().map(|x| value_symbol(*x)).collect());
+                        }
+                    }
+                }
+            }
+        }
+        result.push_str(&format!(":0000:x:{}:{}::\n", grid, deletions.join(" ")));
+    }
+    result
+}
+
+pub fn parse_pm_grid(contents: &str) -> Result<Puzzle, String> {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut box_cols: Option<usize> = None;
+    for line in contents.lines() {
+        // border lines such as .---.---. or :---+---: hold no cells
+        if !line.chars().any(|ch| ch.is_ascii_alphanumeric()) {
+            continue;
+        }
+        if box_cols.is_none() {
+            box_cols = line.split('|').find(|box_row| !box_row.trim().is_empty()).map(|box_row| box_row.split_whitespace().count());
+        }
+        tokens.extend(line.split(|ch: char| ch == '|' || ch.is_whitespace()).filter(|t| !t.is_empty()).map(|t| t.to_string()));
+    }
+    let side_length = (tokens.len() as f64).sqrt().round() as i32;
+    if (side_length * side_length) as usize != tokens.len() {
+        return Err(format!("A pencil mark grid must have a square number of cells, but this one has {}", tokens.len()));
+    }
+    check_side_length(side_length)?;
+
+    let mut puzzle = Puzzle::new(side_length);
+    box_shape_from_separators(&mut puzzle, box_cols);
+    for (i, token) in tokens.iter().enumerate() {
+        let (r, c) = (i / side_length as usize, i % side_length as usize);
+        let (marker, list) = match token.strip_prefix(['+', '.']) {
+            Some(list) => (token.chars().next(), list),
+            None => (None, token.as_str()),
+        };
+        let mut vals = Vec::new();
+        for ch in list.chars() {
+            match symbol_value(ch, side_length)? {
+                Some(v) => vals.push(v),
+                None => return Err(format!("\"{}\" is not a valid list of candidates", token)),
+            }
+        }
+        match (marker, vals.len()) {
+            (Some('+'), 1) => puzzle.entries[r][c] = Some(vals[0]),
+            (Some('+'), _) => return Err(format!("\"{}\" must be a single entered value", token)),
+            (None, 1) => puzzle.givens[r][c] = Some(vals[0]),
+            _ => puzzle.candidates[r][c] = vals,
+        }
+    }
+    Ok(puzzle)
+}
+
+pub fn write_pm_grid(puzzle: &Puzzle) -> String {
+    let side_length = puzzle.side_length as usize;
+    let (box_rows, box_cols) = (puzzle.box_shape.0 as usize, puzzle.box_shape.1 as usize);
+    let allowed = puzzle.rule_candidates();
+    let list = |vals: &[i32]| -> String {
+        match vals {
+            [] => ".".to_string(),
+            [v] => format!(".{}", value_symbol(*v)), // a bare digit would read back as a given
+            _ => vals.iter().map(|v| value_symbol(*v)).collect(),
+        }
+    };
+    let cells: Vec<Vec<String>> = (0..side_length).map(|r| (0..side_length).map(|c| match (puzzle.givens[r][c], puzzle.entries[r][c]) {
+        (Some(v), _) => value_symbol(v).to_string(),
+        (None, Some(v)) => format!("+{}", value_symbol(v)),
+        (None, None) if puzzle.candidates[r][c].is_empty() => list(allowed.as_ref().map_or(&[][..], |all| &all[r][c])),
+        (None, None) => list(&puzzle.candidates[r][c]),
+    }).collect()).collect();
+
+    // every column is as wide as its longest list of candidates
+    let widths: Vec<usize> = (0..side_length).map(|c| (0..side_length).map(|r| cells[r][c].len()).max().unwrap()).collect();
+    let border = |left: char, joint: char, right: char| {
+        let segments: Vec<String> = widths.chunks(box_cols).map(|w| "-".repeat(w.iter().sum::<usize>() + w.len() + 1)).collect();
+        format!("{}{}{}\n", left, segments.join(&joint.to_string()), right)
+    };
+
+    let mut result = border('.', '.', '.');
+    for (r, row) in cells.iter().enumerate() {
+        if r > 0 && r % box_rows == 0 {
+            result.push_str(&border(':', '+', ':'));
+        }
+        result.push('|');
+        for (c, cell) in row.iter().enumerate() {
+            result.push_str(&format!(" {:width$}", cell, width = widths[c]));
+            if (c + 1) % box_cols == 0 {
+                result.push_str(" |");
+            }
+        }
+        result.push('\n');
+    }
+    result.push_str(&border('\'', '\'', '\''));
+    result
+}
+
+// Reads a square written as r<row>c<col>, counting from 1 like puzzle setters do
+fn parse_cell(token: &str, side_length: i32) -> Result<(usize, usize), String> {
+    let cell = token.to_lowercase().strip_prefix('r')
+        .and_then(|rest| rest.split_once('c'))
+        .and_then(|(r, c)| Some((r.parse::<usize>().ok()?, c.parse::<usize>().ok()?)))
+        .filter(|&(r, c)| r >= 1 && c >= 1 && r <= side_length as usize && c <= side_length as usize);
+    cell.map(|(r, c)| (r - 1, c - 1)).ok_or_else(|| format!("\"{}\" is not a square of a {}x{} puzzle", token, side_length, side_length))
+}
+
+fn cell_name((row, col): (usize, usize)) -> String {
+    format!("r{}c{}", row + 1, col + 1)
+}
+
+// Save games and variant puzzle files are made of `key value` lines, in any order after the first, with blank lines
+// and lines starting with `#` ignored. Squares are written rNcM, counting rows and columns from 1 at the top left.
+//  - `size N`: the width and height of the grid. Must come first.
+//  - `box RxC`: the shape of the boxes, R rows by C columns.
+//  - `regions <N*N symbols>`: jigsaw regions, one symbol per square going across each row, naming its region.
+//  - `rule <name>`: a rule covering the whole grid: diagonals, windows, antiknight, antiking, nonconsecutive,
+//    alldots (every Kropki dot is given) or allxv (every X and V is given).
+//  - `cage <sum> <squares>`: a killer cage.
+//  - `thermo <squares>`: a thermometer, from the bulb to the tip.
+//  - `arrow <circle squares> -> <shaft squares>`: the shaft adds up to the number read across the circle.
+//  - `line <whisper|renban|palindrome> <squares>`: a line, from one end to the other.
+//  - `edge <white|black|x|v|less> <square> <square>`: a marker between two squares next to each other. A less marker
+//    says the first square is smaller.
+//  - `parity <odd|even> <square>`: a square with an odd or even value.
+//  - `sandwich <sum> <squares>` and `littlekiller <sum> <squares>`: a clue outside the grid, with the row, column
+//    or diagonal it looks along listed starting from the square next to it.
+//  - `symbols <name>`: the symbols values are shown with, when not the usual ones for the size.
+//  - `title`, `author`, `source`, `difficulty` and `comment`, each followed by a line of text.
+//  - `rules <text>`: the rules of the puzzle, written out for people. Repeat it for rules of several lines.
+//  - `givens <N*N symbols>`: the starting values, `.` for a blank square.
+//  - `entries <N*N symbols>`, `candidates <N*N lists>` and `elapsed <seconds>`: the player's progress. These are
+//    only allowed in save games, a variant file that has them is rejected rather than handing someone else's
+//    progress to whoever opens it.
+pub fn parse_save_game(contents: &str) -> Result<Puzzle, String> {
+    parse_key_values(contents, true)
+}
+
+pub fn parse_variant(contents: &str) -> Result<Puzzle, String> {
+    parse_key_values(contents, false)
+}
+
+fn parse_key_values(contents: &str, progress: bool) -> Result<Puzzle, String> {
+    let mut puzzle: Option<Puzzle> = None;
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+        if key == "size" {
+            let side_length: i32 = rest.parse().map_err(|_| format!("\"{}\" is not a valid size", rest))?;
+            check_side_length(side_length)?;
+            puzzle = Some(Puzzle::new(side_length));
+            continue;
+        }
+        let puzzle = puzzle.as_mut().ok_or("A save game must start with its size")?;
+        if !progress && ["elapsed", "entries", "candidates"].contains(&key) {
+            return Err(format!("A .variant file only describes the puzzle, so it can't have \"{}\"", key));
+        }
+        let side_length = puzzle.side_length;
+        match key {
+            "box" => {
+                let shape = rest.split_once('x').and_then(|(r, c)| Some((r.parse().ok()?, c.parse().ok()?)));
+                puzzle.box_shape = shape.filter(|(r, c): &(i32, i32)| *r > 0 && r * c == side_length)
+                    .ok_or_else(|| format!("\"{}\" is not a valid box shape for a {}x{} puzzle", rest, side_length, side_length))?;
+            }
+            "rule" => match rest {
+                "diagonals" => puzzle.constraints.push(Constraint::Diagonals),
+                "windows" => puzzle.constraints.push(Constraint::Windows),
+                "antiknight" => puzzle.constraints.push(Constraint::AntiKnight),
+                "antiking" => puzzle.constraints.push(Constraint::AntiKing),
+                "nonconsecutive" => puzzle.constraints.push(Constraint::NonConsecutive),
+                "alldots" => puzzle.constraints.push(Constraint::AllDotsGiven),
+                "allxv" => puzzle.constraints.push(Constraint::AllXVGiven),
+                _ => return Err(format!("Unknown rule \"{}\"", rest)),
+            },
+            "cage" | "sandwich" | "littlekiller" => {
+                let mut tokens = rest.split_whitespace();
+                let sum = tokens.next().and_then(|t| t.parse().ok()).ok_or_else(|| format!("\"{}\" does not start with the sum", rest))?;
+                let cells = tokens.map(|t| parse_cell(t, side_length)).collect::<Result<Vec<_>, _>>()?;
+                puzzle.constraints.push(match key {
+                    "cage" => Constraint::Cage { cells, sum },
+                    "sandwich" => Constraint::Sandwich { cells, sum },
+                    _ => Constraint::LittleKiller { cells, sum },
+                });
+            }
+            "arrow" => {
+                let (circle, shaft) = rest.split_once("->").ok_or_else(|| format!("\"{}\" needs a -> between the circle and the shaft", rest))?;
+                let circle = circle.split_whitespace().map(|t| parse_cell(t, side_length)).collect::<Result<Vec<_>, _>>()?;
+                let cells = shaft.split_whitespace().map(|t| parse_cell(t, side_length)).collect::<Result<Vec<_>, _>>()?;
+                puzzle.constraints.push(Constraint::Arrow { circle, cells });
+            }
+            "line" => {
+                let mut tokens = rest.split_whitespace();
+                let kind = tokens.next().and_then(|name| LineKind::from_name(&name.to_lowercase()))
+                    .ok_or_else(|| format!("\"{}\" does not start with whisper, renban or palindrome", rest))?;
+                let cells = tokens.map(|t| parse_cell(t, side_length)).collect::<Result<Vec<_>, _>>()?;
+                puzzle.constraints.push(Constraint::Line { kind, cells });
+            }
+            "parity" => {
+                let (kind, cell) = rest.split_once(' ').ok_or_else(|| format!("\"{}\" must be odd or even, then the square", rest))?;
+                let even = match kind.to_lowercase().as_str() {
+                    "even" => true,
+                    "odd" => false,
+                    _ => return Err(format!("\"{}\" does not start with odd or even", rest)),
+                };
+                puzzle.constraints.push(Constraint::Parity { cell: parse_cell(cell.trim(), side_length)?, even });
+            }
+            "edge" => {
+                let tokens: Vec<&str> = rest.split_whitespace().collect();
+                let marker = tokens.first().and_then(|name| EdgeMarker::from_name(&name.to_lowercase()))
+                    .ok_or_else(|| format!("\"{}\" does not start with white, black, x, v or less", rest))?;
+                if tokens.len() != 3 {
+                    return Err(format!("\"{}\" must name the two squares the marker is between", rest));
+                }
+                let cells = [parse_cell(tokens[1], side_length)?, parse_cell(tokens[2], side_length)?];
+                puzzle.constraints.push(Constraint::Edge { marker, cells });
+            }
+            "thermo" => {
+                let cells = rest.split_whitespace().map(|t| parse_cell(t, side_length)).collect::<Result<Vec<_>, _>>()?;
+                puzzle.constraints.push(Constraint::Thermometer { cells });
+            }
+            "regions" => {
+                let regions = parse_line(rest)?;
+                if regions.len() != side_length as usize || regions.iter().flatten().any(|region| region.is_none()) {
+                    return Err("Every square must be in a region".to_string());
+                }
+                puzzle.regions = Some(regions.iter().map(|row| row.iter().map(|region| region.unwrap() as usize - 1).collect()).collect());
+                puzzle.check_regions()?;
+            }
+            "symbols" => {
+                let symbols = SymbolSet::from_name(rest).ok_or_else(|| format!("\"{}\" is not a valid symbol set", rest))?;
+                if !symbols.supports(side_length) {
+                    return Err(format!("There are not enough {} symbols for a {}x{} puzzle", symbols.name(), side_length, side_length));
+                }
+                puzzle.symbols = symbols;
+            }
+            "elapsed" => puzzle.elapsed_secs = rest.parse().map_err(|_| format!("\"{}\" is not a valid elapsed time", rest))?,
+            "givens" => puzzle.givens = parse_line(rest)?,
+            "entries" => puzzle.entries = parse_line(rest)?,
+            "candidates" => {
+                let tokens: Vec<&str> = rest.split_whitespace().collect();
+                if tokens.len() != (side_length * side_length) as usize {
+                    return Err(format!("Expected {} lists of candidates", side_length * side_length));
+                }
+                for (i, token) in tokens.iter().enumerate() {
+                    let vals = token.chars().map(|ch| symbol_value(ch, side_length)).collect::<Result<Vec<_>, _>>()?;
+                    puzzle.candidates[i / side_length as usize][i % side_length as usize] = vals.into_iter().flatten().collect();
+                }
+            }
+            "title" => puzzle.metadata.title = Some(rest.to_string()),
+            "author" => puzzle.metadata.author = Some(rest.to_string()),
+            "source" => puzzle.metadata.source = Some(rest.to_string()),
+            "difficulty" => puzzle.metadata.difficulty = Some(rest.to_string()),
+            "comment" => puzzle.metadata.comment = Some(rest.to_string()),
+            "rules" => {
+                let rules = puzzle.metadata.rules.get_or_insert_with(String::new);
+                if !rules.is_empty() {
+                    rules.push('\n');
+                }
+                rules.push_str(rest);
+            }
+            _ => return Err(format!("Unknown save game field \"{}\"", key)),
+        }
+    }
+    let puzzle = puzzle.ok_or("The file does not contain a puzzle")?;
+    puzzle.check()?;
+    Ok(puzzle)
+}
+
+pub fn write_save_game(puzzle: &Puzzle) -> String {
+    write_key_values(puzzle, true)
+}
+
+// Writes just the puzzle, leaving out how far anyone has got with it
+pub fn write_variant(puzzle: &Puzzle) -> String {
+    write_key_values(puzzle, false)
+}
+
+fn write_key_values(puzzle: &Puzzle, progress: bool) -> String {
+    let side_length = puzzle.side_length;
+    let mut result = format!("size {}\nbox {}x{}\n", side_length, puzzle.box_shape.0, puzzle.box_shape.1);
+    if progress {
+        result.push_str(&format!("elapsed {}\n", puzzle.elapsed_secs));
+    }
+    // Regions come before the constraints, which are checked against them when the file is read back
+    if let Some(regions) = &puzzle.regions {
+        let symbols: String = regions.iter().flatten().map(|region| value_symbol(*region as i32 + 1)).collect();
+        result.push_str(&format!("regions {}\n", symbols));
+    }
+    for constraint in &puzzle.constraints {
+        match constraint {
+            Constraint::Diagonals => result.push_str("rule diagonals\n"),
+            Constraint::Windows => result.push_str("rule windows\n"),
+            Constraint::AntiKnight => result.push_str("rule antiknight\n"),
+            Constraint::AntiKing => result.push_str("rule antiking\n"),
+            Constraint::NonConsecutive => result.push_str("rule nonconsecutive\n"),
+            Constraint::AllDotsGiven => result.push_str("rule alldots\n"),
+            Constraint::AllXVGiven => result.push_str("rule allxv\n"),
+            Constraint::Cage { cells, sum } | Constraint::Sandwich { cells, sum } | Constraint::LittleKiller { cells, sum } => {
+                let key = match constraint {
+                    Constraint::Cage { .. } => "cage",
+                    Constraint::Sandwich { .. } => "sandwich",
+                    _ => "littlekiller",
+                };
+                let cells: Vec<String> = cells.iter().map(|&cell| cell_name(cell)).collect();
+                result.push_str(&format!("{} {} {}\n", key, sum, cells.join(" ")));
+            }
+            Constraint::Thermometer { cells } => {
+                let cells: Vec<String> = cells.iter().map(|&cell| cell_name(cell)).collect();
+                result.push_str(&format!("thermo {}\n", cells.join(" ")));
+            }
+            Constraint::Parity { cell, even } => result.push_str(&format!("parity {} {}\n", if *even {"even"} else {"odd"}, cell_name(*cell))),
+            Constraint::Line { kind, cells } => {
+                let cells: Vec<String> = cells.iter().map(|&cell| cell_name(cell)).collect();
+                result.push_str(&format!("line {} {}\n", kind.name(), cells.join(" ")));
+            }
+            Constraint::Edge { marker, cells: [a, b] } => result.push_str(&format!("edge {} {} {}\n", marker.name(), cell_name(*a), cell_name(*b))),
+            Constraint::Arrow { circle, cells } => {
+                let circle: Vec<String> = circle.iter().map(|&cell| cell_name(cell)).collect();
+                let cells: Vec<String> = cells.iter().map(|&cell| cell_name(cell)).collect();
+                result.push_str(&format!("arrow {} -> {}\n", circle.join(" "), cells.join(" ")));
+            }
+        }
+    }
+    if puzzle.symbols != SymbolSet::default_for(side_length) {
+        result.push_str(&format!("symbols {}\n", puzzle.symbols.name()));
+    }
+    let fields = [
+        ("title", &puzzle.metadata.title),
+        ("author", &puzzle.metadata.author),
+        ("source", &puzzle.metadata.source),
+        ("difficulty", &puzzle.metadata.difficulty),
+        ("comment", &puzzle.metadata.comment),
+    ];
+    for (key, text) in fields {
+        if let Some(text) = text {
+            result.push_str(&format!("{} {}\n", key, text.replace('\n', " ")));
+        }
+    }
+    if let Some(rules) = &puzzle.metadata.rules {
+        for line in rules.lines() {
+            result.push_str(format!("rules {}", line).trim_end());
+            result.push('\n');
+        }
+    }
+    result.push_str(&format!("givens {}\n", write_rows(&puzzle.givens, None).replace('\n', "")));
+    if !progress {
+        return result;
+    }
+    result.push_str(&format!("entries {}\n", write_rows(&puzzle.entries, None).replace('\n', "")));
+    let candidates: Vec<String> = puzzle.candidates.iter().flatten().map(|vals| {
+        if vals.is_empty() {
+            ".".to_string()
+        } else {
+            vals.iter().map(|v| value_symbol(*v)).collect()
+        }
+    }).collect();
+    result.push_str(&format!("candidates {}\n", candidates.join(" ")));
+    result
+}
+
+// Whether a file is meant for overlapping grids, which go in .multi files
+pub fn is_multigrid_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("multi"))
+}
+
+pub fn load_multigrid_file(path: &Path) -> Result<MultiPuzzle, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+    parse_multigrid(&contents)
+}
+
+pub fn save_multigrid_file(path: &Path, puzzle: &MultiPuzzle) -> Result<(), String> {
+    fs::write(path, write_multigrid(puzzle)).map_err(|e| format!("Could not write {}: {}", path.display(), e))
+}
+
+// Multigrid files are `key value` lines like save games, with blank lines and lines starting with `#` ignored:
+//  - `layout <samurai|butterfly|twin>`: how the grids are put together. Must come first.
+//  - `givens <symbols>`: the starting values of every square of the canvas, going across each row, with `.` for a
+//    blank square and for the squares between grids.
+pub fn parse_multigrid(contents: &str) -> Result<MultiPuzzle, String> {
+    let mut puzzle: Option<MultiPuzzle> = None;
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+        if key == "layout" {
+            let layout = Layout::from_name(rest).ok_or_else(|| format!("\"{}\" is not a layout of overlapping grids", rest))?;
+            puzzle = Some(MultiPuzzle::new(layout));
+            continue;
+        }
+        let puzzle = puzzle.as_mut().ok_or("A multigrid file must start with its layout")?;
+        match key {
+            "givens" => {
+                let n = puzzle.layout.canvas_size();
+                let chars: Vec<char> = rest.chars().collect();
+                if chars.len() != n * n {
+                    return Err(format!("Expected {} squares in the givens of a {} puzzle", n * n, puzzle.layout.name()));
+                }
+                for (i, ch) in chars.into_iter().enumerate() {
+                    puzzle.givens[i / n][i % n] = symbol_value(ch, multigrid::GRID_SIZE as i32)?;
+                }
+            }
+            _ => return Err(format!("Unknown multigrid field \"{}\"", key)),
+        }
+    }
+    let puzzle = puzzle.ok_or("The file does not contain a puzzle")?;
+    puzzle.check()?;
+    Ok(puzzle)
+}
+
+pub fn write_multigrid(puzzle: &MultiPuzzle) -> String {
+    format!("layout {}\ngivens {}\n", puzzle.layout.name(), write_rows(&puzzle.givens, None).replace('\n', ""))
+}
+
+#[cfg(feature = "serde")]
+pub fn parse_json(contents: &str) -> Result<Vec<Puzzle>, String> {
+    // a file can hold either a single puzzle or a list of them
+    let puzzles: Vec<Puzzle> = if contents.trim_start().starts_with('[') {
+        serde_json::from_str(contents).map_err(|e| format!("Invalid puzzle JSON: {}", e))?
+    } else {
+        serde_json::from_str(contents).map(|p| vec![p]).map_err(|e| format!("Invalid puzzle JSON: {}", e))?
+    };
+    if puzzles.is_empty() {
+        return Err("The file does not contain any puzzles".to_string());
+    }
+    // nothing stops a JSON file from having grids of the wrong size, so it gets every check the other formats make
+    for puzzle in &puzzles {
+        puzzle.check()?;
+    }
+    Ok(puzzles)
+}
+
+#[cfg(feature = "serde")]
+pub fn write_json(puzzles: &[Puzzle]) -> Result<String, String> {
+    let result = match puzzles {
+        [p] => serde_json::to_string_pretty(p),
+        _ => serde_json::to_string_pretty(puzzles),
+    };
+    result.map_err(|e| format!("Could not convert the puzzle to JSON: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLASSIC: &str = "53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79";
+
+    fn classic() -> Puzzle {
+        parse_sdm(CLASSIC).unwrap().remove(0)
+    }
+
+    // The classic puzzle part way through: two squares filled in and some pencil marks crossed out
+    fn in_progress() -> Puzzle {
+        let mut puzzle = classic();
+        puzzle.entries[0][2] = Some(4);
+        puzzle.entries[8][0] = Some(3);
+        let mut candidates = puzzle.rule_candidates().unwrap();
+        candidates[0][3].retain(|v| *v != 6);
+        candidates[4][4] = vec![5];
+        puzzle.candidates = candidates;
+        puzzle
+    }
+
+    #[test]
+    fn sdk_round_trip() {
+        let mut puzzle = in_progress();
+        puzzle.candidates = Puzzle::new(9).candidates;
+        puzzle.metadata.title = Some("Classic".to_string());
+        puzzle.metadata.author = Some("Unknown".to_string());
+        assert_eq!(parse_sdk(&write_sdk(&puzzle)).unwrap(), puzzle);
+    }
+
+    #[test]
+    fn ss_round_trip() {
+        let puzzle = classic();
+        let written = write_ss(&puzzle);
+        assert!(written.contains('|') && written.contains('!'));
+        assert_eq!(parse_ss(&written).unwrap().givens, puzzle.givens);
+    }
+
+    #[test]
+    fn sdm_round_trip() {
+        let mut other = classic();
+        other.givens[0][2] = Some(4);
+        let puzzles = vec![classic(), other];
+        let read = parse_sdm(&write_sdm(&puzzles)).unwrap();
+        assert_eq!(read.iter().map(|p| &p.givens).collect::<Vec<_>>(), puzzles.iter().map(|p| &p.givens).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn hodoku_round_trip() {
+        let puzzle = in_progress();
+        let read = parse_hodoku(&write_hodoku(std::slice::from_ref(&puzzle))).unwrap().remove(0);
+        assert_eq!(read.givens, puzzle.givens);
+        assert_eq!(read.entries, puzzle.entries);
+        assert_eq!(read.candidates, puzzle.candidates);
+    }
+
+    #[test]
+    fn pm_grid_round_trip() {
+        let puzzle = in_progress();
+        let read = parse_pm_grid(&write_pm_grid(&puzzle)).unwrap();
+        assert_eq!(read.givens, puzzle.givens);
+        assert_eq!(read.entries, puzzle.entries);
+        assert_eq!(read.candidates, puzzle.candidates);
+
+        // without pencil marks, every square is written with the candidates the rules leave it
+        let puzzle = classic();
+        let read = parse_pm_grid(&write_pm_grid(&puzzle)).unwrap();
+        assert_eq!(read.givens, puzzle.givens);
+        assert_eq!(Some(read.candidates), puzzle.rule_candidates());
+    }
+
+    #[test]
+    fn pm_grid_of_a_broken_puzzle() {
+        // with a value repeated in a row there are no candidates to write, so every empty square is a lone `.`
+        let mut puzzle = classic();
+        puzzle.givens[0][2] = Some(5);
+        let written = write_pm_grid(&puzzle);
+        let read = parse_pm_grid(&written).unwrap();
+        assert_eq!(read.givens, puzzle.givens);
+        assert!(read.candidates.iter().flatten().all(|vals| vals.is_empty()));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_is_checked() {
+        let puzzle = in_progress();
+        assert_eq!(parse_json(&write_json(std::slice::from_ref(&puzzle)).unwrap()).unwrap(), vec![puzzle.clone()]);
+        assert!(parse_json("[]").is_err());
+
+        let mut short = puzzle.clone();
+        short.givens.pop();
+        assert!(parse_json(&write_json(&[short]).unwrap()).is_err());
+        let mut bad_box = puzzle.clone();
+        bad_box.box_shape = (0, 9);
+        assert!(parse_json(&write_json(&[bad_box]).unwrap()).is_err());
+        let mut bad_value = puzzle.clone();
+        bad_value.candidates[0][2] = vec![10];
+        assert!(parse_json(&write_json(&[bad_value]).unwrap()).is_err());
+        let mut bad_regions = puzzle;
+        bad_regions.regions = Some(vec![vec![0; 9]; 9]);
+        assert!(parse_json(&write_json(&[bad_regions]).unwrap()).is_err());
+    }
+
+    #[test]
+    fn save_game_round_trip() {
+        let mut puzzle = in_progress();
+        puzzle.elapsed_secs = 95;
+        puzzle.metadata.title = Some("Classic".to_string());
+        assert_eq!(parse_save_game(&write_save_game(&puzzle)).unwrap(), puzzle);
+    }
+
+    #[test]
+    fn save_game_is_checked() {
+        let written = write_save_game(&classic());
+        assert!(parse_save_game(&written.replace("givens 53", "givens 5")).is_err());
+        assert!(parse_save_game(&written.replace("box 3x3", "box 1x3")).is_err());
+        assert!(parse_save_game(&written.replace("givens", "regions 111111111222222222\ngivens")).is_err());
+    }
+
+    #[test]
+    fn variant_round_trip() {
+        let mut puzzle = classic();
+        puzzle.constraints.push(Constraint::Cage { cells: vec![(0, 2), (0, 3)], sum: 7 });
+        puzzle.constraints.push(Constraint::Line { kind: LineKind::Renban, cells: vec![(3, 3), (3, 4), (3, 5)] });
+        puzzle.metadata.rules = Some("Normal sudoku rules apply.\n\nCages add up to their sums.".to_string());
+        assert_eq!(parse_variant(&write_variant(&puzzle)).unwrap(), puzzle);
+
+        // progress is left out when writing, and refused when reading
+        let written = write_variant(&in_progress());
+        assert!(!written.contains("entries") && !written.contains("candidates") && !written.contains("elapsed"));
+        assert!(parse_variant(&write_save_game(&in_progress())).is_err());
+    }
+
+    #[test]
+    fn jigsaw_round_trip() {
+        // each row is a region, so the inequality only fits inside a region once the regions are read
+        let mut puzzle = Puzzle::new(4);
+        puzzle.regions = Some((0..4).map(|r| vec![r; 4]).collect());
+        puzzle.constraints.push(Constraint::Edge { marker: EdgeMarker::Less, cells: [(0, 1), (0, 2)] });
+        puzzle.check().unwrap();
+        assert_eq!(parse_save_game(&write_save_game(&puzzle)).unwrap(), puzzle);
+        assert_eq!(parse_variant(&write_variant(&puzzle)).unwrap(), puzzle);
+    }
+
+    #[test]
+    fn multigrid_round_trip() {
+        let mut puzzle = MultiPuzzle::new(Layout::Samurai);
+        puzzle.givens[0][0] = Some(5);
+        puzzle.givens[10][10] = Some(9);
+        puzzle.givens[20][12] = Some(1);
+        let written = write_multigrid(&puzzle);
+        assert!(written.starts_with("layout samurai\n"));
+        assert_eq!(parse_multigrid(&written).unwrap(), puzzle);
+        assert!(parse_multigrid(&written.replace("samurai", "twin")).is_err()); // the givens are for a bigger canvas
+        assert!(parse_multigrid(&written.replace("samurai", "flower")).is_err());
+        assert!(parse_multigrid(&written.replacen('.', "1", 10)).is_err()); // a given between the top grids
+        assert!(load_file(Path::new("samurai.multi")).is_err() && save_file(Path::new("samurai.multi"), &[classic()]).is_err());
+    }
+
+    #[test]
+    fn detects_formats() {
+        let puzzle = in_progress();
+        assert_eq!(Format::detect(&write_pm_grid(&puzzle)), Format::PencilMarkGrid);
+        assert_eq!(Format::detect(&write_hodoku(std::slice::from_ref(&puzzle))), Format::HoDoKu);
+        assert_eq!(Format::detect(&write_ss(&puzzle)), Format::SimpleSudoku);
+        assert_eq!(Format::detect(&write_sdm(&[classic(), classic()])), Format::Sdm);
+        assert_eq!(Format::detect(&write_sdk(&classic())), Format::Sdk);
+        assert_eq!(Format::detect(&write_save_game(&puzzle)), Format::SaveGame);
+        assert_eq!(Format::detect(&write_variant(&puzzle)), Format::Variant);
+    }
+}
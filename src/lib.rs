@@ -0,0 +1,9 @@
+pub mod solver;
+pub mod symbols;
+pub mod rules;
+pub mod puzzle;
+pub mod formats;
+pub mod export;
+pub mod raster;
+pub mod photo;
+pub mod multigrid;
@@ -7,65 +7,338 @@ extern crate conrod_winit;
 extern crate glium;
 
 mod support;
-mod solver;
+mod cli;
 
-use conrod_core::{widget, Positionable, Widget, Sizeable, Labelable};
+use conrod_core::{widget, Colorable, Positionable, Widget, Sizeable, Labelable};
 use glium::Surface;
 
-use solver::CheckablySquare;
+use sudoku_solver::{export, formats, multigrid, photo, puzzle, rules, solver};
+use sudoku_solver::symbols::SymbolSet;
 
 use std::cmp;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 const WIDTH: u32 = 1200;
 const HEIGHT: u32 = 800;
-const MAX_SIDE_LENGTH: i32 = 16;
+const MAX_SIDE_LENGTH: i32 = 49;
 const MIN_SIDE_LENGTH: i32 = 4;
+const SHADE_COLOR: conrod_core::Color = conrod_core::Color::Rgba(0.86, 0.86, 0.86, 1.0);
+const PARITY_COLOR: conrod_core::Color = conrod_core::Color::Rgba(0.5, 0.5, 0.5, 0.3);
+const THERMOMETER_COLOR: conrod_core::Color = conrod_core::Color::Rgba(0.5, 0.5, 0.5, 0.4); // see through, so values still show
 
-// following function from https://stackoverflow.com/questions/50277050/is-there-a-built-in-function-that-converts-a-number-to-a-string-in-any-base
-fn format_radix(mut x: u32, radix: u32) -> String {
-    let mut result = vec![];
+// The kinds of constraint drawn by clicking their squares on the board
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Drawing {
+    Cage,
+    Thermometer,
+    Line,
+    ArrowCircle,
+    ArrowShaft,
+    Edge, // markers between pairs of squares, added as soon as both are clicked
+    Clue, // clues outside the grid, added as soon as their square around the board is clicked
+    Parity, // odd or even squares, shaded as soon as they are clicked
+}
+
+// Whether two squares are next to each other, including diagonally
+fn touching(a: (usize, usize), b: (usize, usize)) -> bool {
+    a != b && a.0.abs_diff(b.0) <= 1 && a.1.abs_diff(b.1) <= 1
+}
 
-    loop {
-        let m = x % radix;
-        x = x / radix;
+// Whether squares make up one straight row or column with no gaps, like the pill of an arrow
+fn in_a_line(cells: &[(usize, usize)]) -> bool {
+    let mut cells = cells.to_vec();
+    cells.sort();
+    let across = cells.windows(2).all(|pair| pair[0].0 == pair[1].0 && pair[0].1 + 1 == pair[1].1);
+    let down = cells.windows(2).all(|pair| pair[0].1 == pair[1].1 && pair[0].0 + 1 == pair[1].0);
+    across || down
+}
+
+fn marker_label(marker: puzzle::EdgeMarker) -> &'static str {
+    match marker {
+        puzzle::EdgeMarker::WhiteDot => "Marker: White Dot",
+        puzzle::EdgeMarker::BlackDot => "Marker: Black Dot",
+        puzzle::EdgeMarker::X => "Marker: X",
+        puzzle::EdgeMarker::V => "Marker: V",
+        puzzle::EdgeMarker::Less => "Marker: <",
+    }
+}
+
+fn next_marker(marker: puzzle::EdgeMarker) -> puzzle::EdgeMarker {
+    match marker {
+        puzzle::EdgeMarker::WhiteDot => puzzle::EdgeMarker::BlackDot,
+        puzzle::EdgeMarker::BlackDot => puzzle::EdgeMarker::X,
+        puzzle::EdgeMarker::X => puzzle::EdgeMarker::V,
+        puzzle::EdgeMarker::V => puzzle::EdgeMarker::Less,
+        puzzle::EdgeMarker::Less => puzzle::EdgeMarker::WhiteDot,
+    }
+}
+
+fn line_label(kind: puzzle::LineKind) -> &'static str {
+    match kind {
+        puzzle::LineKind::Whisper => "Line: Whisper",
+        puzzle::LineKind::Renban => "Line: Renban",
+        puzzle::LineKind::Palindrome => "Line: Palindrome",
+    }
+}
 
-        // will panic if you use a bad radix (< 2 or > 36).
-        result.push(std::char::from_digit(m, radix).unwrap());
-        if x == 0 {
-            break;
+fn next_line(kind: puzzle::LineKind) -> puzzle::LineKind {
+    match kind {
+        puzzle::LineKind::Whisper => puzzle::LineKind::Renban,
+        puzzle::LineKind::Renban => puzzle::LineKind::Palindrome,
+        puzzle::LineKind::Palindrome => puzzle::LineKind::Whisper,
+    }
+}
+
+// The colour a line is drawn in on the board, as in exports but see through, so values still show
+fn line_color(kind: puzzle::LineKind) -> conrod_core::Color {
+    let (r, g, b) = export::line_color(kind);
+    conrod_core::Color::Rgba(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 0.6)
+}
+
+// The kind of outside clue added by clicking around the board: None for a sandwich sum, or the (row, col) step of a
+// little killer's diagonal
+fn clue_label(step: Option<(i64, i64)>) -> &'static str {
+    match step {
+        None => "Clue: Sandwich",
+        Some((1, 1)) => "Clue: Killer Down Right",
+        Some((1, -1)) => "Clue: Killer Down Left",
+        Some((-1, -1)) => "Clue: Killer Up Left",
+        Some(_) => "Clue: Killer Up Right",
+    }
+}
+
+fn next_clue(step: Option<(i64, i64)>) -> Option<(i64, i64)> {
+    match step {
+        None => Some((1, 1)),
+        Some((1, 1)) => Some((1, -1)),
+        Some((1, -1)) => Some((-1, -1)),
+        Some((-1, -1)) => Some((-1, 1)),
+        Some(_) => None,
+    }
+}
+
+// Adds an outside clue at `clue` (a square around the board), or takes away the one already there going the same way.
+// A sandwich goes across the row or column next to its clue, a little killer along the diagonal from it.
+fn toggle_clue(constraints: &mut Vec<puzzle::Constraint>, side_length: i32, clue: (i64, i64), step: Option<(i64, i64)>, sum_str: &str) -> Result<String, String> {
+    let n = side_length as i64;
+    let step = match step {
+        Some(step) => step,
+        None if clue.0 == -1 && clue.1 >= 0 && clue.1 < n => (1, 0),
+        None if clue.0 == n && clue.1 >= 0 && clue.1 < n => (-1, 0),
+        None if clue.1 == -1 && clue.0 >= 0 && clue.0 < n => (0, 1),
+        None if clue.1 == n && clue.0 >= 0 && clue.0 < n => (0, -1),
+        None => return Err("Sandwich clues go next to a row or column".to_string()),
+    };
+    let existing = constraints.iter().position(|constraint| matches!(constraint.outside_clue(), Some(outside) if outside.at == clue && outside.step == step));
+    if let Some(i) = existing {
+        constraints.remove(i);
+        return Ok("Clue taken away".to_string());
+    }
+    let sum = sum_str.trim().parse::<i32>().map_err(|_| "Type the clue first".to_string())?;
+    let cells = puzzle::outside_line(side_length, clue, step);
+    if cells.len() < 2 {
+        return Err("That diagonal is too short for a clue".to_string());
+    }
+    constraints.push(if step.0 != 0 && step.1 != 0 {
+        puzzle::Constraint::LittleKiller { cells, sum }
+    } else {
+        puzzle::Constraint::Sandwich { cells, sum }
+    });
+    Ok("Clue added!".to_string())
+}
+
+// Puts a marker between two squares next to each other, replacing whatever marker was there. Putting the same marker
+// there again takes it away, while a < put the other way round flips it.
+fn toggle_edge(constraints: &mut Vec<puzzle::Constraint>, marker: puzzle::EdgeMarker, a: (usize, usize), b: (usize, usize)) {
+    let existing = constraints.iter().position(|constraint| matches!(constraint,
+        puzzle::Constraint::Edge { cells, .. } if *cells == [a, b] || *cells == [b, a]));
+    if let Some(i) = existing {
+        if constraints.remove(i) == (puzzle::Constraint::Edge { marker, cells: [a, b] }) {
+            return;
         }
     }
-    result.into_iter().rev().collect()
+    constraints.push(puzzle::Constraint::Edge { marker, cells: [a, b] });
 }
 
+// Marks a square as odd or even, replacing whatever it was marked as. Marking it the same way again takes it away.
+fn toggle_parity(constraints: &mut Vec<puzzle::Constraint>, cell: (usize, usize), even: bool) {
+    let existing = constraints.iter().position(|constraint| matches!(constraint, puzzle::Constraint::Parity { cell: at, .. } if *at == cell));
+    if let Some(i) = existing {
+        if constraints.remove(i) == (puzzle::Constraint::Parity { cell, even }) {
+            return;
+        }
+    }
+    constraints.push(puzzle::Constraint::Parity { cell, even });
+}
 
-fn update_square_str(s: String, side_length: i32) -> String {
-    if s.len() > 0 {
-        let result: Result<i32, _> = i32::from_str_radix(&s, (side_length + 1) as u32);//s.parse();
-        return match result {
-            Err(_) => "".to_string(),
-            Ok(num) => if num >= 1 && num <= side_length {format!("{}", s).to_uppercase()} else {"".to_string()}
-        };
+// Keeps a square's text if it is a value, or could still become one as more is typed (like the 1 of 12)
+fn update_square_str(s: String, symbols: &SymbolSet, side_length: i32) -> String {
+    if !s.is_empty() {
+        match symbols.value(&s, side_length) {
+            Some(num) => symbols.symbol(num),
+            None => if symbols.is_prefix(s.trim(), side_length) {s.trim().to_string()} else {"".to_string()}
+        }
     } else {
-        return "".to_string();
+        "".to_string()
+    }
+}
+
+// Keeps only valid values in a list of pencil marks, each at most once and in increasing order.
+// Symbols longer than a character are separated by spaces, and the one still being typed is left as it is.
+fn update_pencil_str(s: String, symbols: &SymbolSet, side_length: i32) -> String {
+    if symbols.is_single_char(side_length) {
+        let mut vals = symbols.parse_list(&s, side_length);
+        vals.sort();
+        vals.dedup();
+        return symbols.format_list(&vals, side_length);
+    }
+    let finished = s.ends_with(|ch: char| ch.is_whitespace() || ch == ',');
+    let tokens: Vec<&str> = s.split(|ch: char| ch.is_whitespace() || ch == ',').filter(|t| !t.is_empty()).collect();
+    let mut kept: Vec<String> = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        match symbols.value(token, side_length) {
+            Some(v) if !kept.contains(&symbols.symbol(v)) => kept.push(symbols.symbol(v)),
+            None if i + 1 == tokens.len() && !finished && symbols.is_prefix(token, side_length) => kept.push(token.to_string()),
+            _ => {}
+        }
+    }
+    let mut result = kept.join(" ");
+    if finished && !result.is_empty() {
+        result.push(' ');
+    }
+    result
+}
+
+// The next kind of symbols the GUI offers that has enough symbols for the puzzle
+fn next_symbols(symbols: &SymbolSet, side_length: i32) -> SymbolSet {
+    let choices = [SymbolSet::Alphanumeric, SymbolSet::Letters, SymbolSet::Numbers];
+    let current = choices.iter().position(|s| s == symbols).unwrap_or(choices.len() - 1);
+    (1..=choices.len()).map(|i| choices[(current + i) % choices.len()].clone()).find(|s| s.supports(side_length)).unwrap()
+}
+
+fn symbols_label(symbols: &SymbolSet) -> String {
+    match symbols {
+        SymbolSet::Alphanumeric => "Symbols: 1-9, A-Z".to_string(),
+        SymbolSet::Letters => "Symbols: A-Z".to_string(),
+        SymbolSet::Numbers => "Symbols: 1, 2, 3...".to_string(),
+        SymbolSet::Custom(_) => "Symbols: Custom".to_string(),
     }
 }
 
-fn fill_solved_values(board_str: &mut Vec<Vec<String>>, side_length: i32, success_str: &mut String) {
-    let empty_values: Vec<Vec<Option<i32>>> = vec![vec![None; side_length as usize]; side_length as usize];
-    let row_vals: Vec<Vec<bool>> = vec![vec![false; side_length as usize]; side_length as usize];
-    let col_vals: Vec<Vec<bool>> = vec![vec![false; side_length as usize]; side_length as usize];
-    let box_vals: Vec<Vec<bool>> = vec![vec![false; side_length as usize]; side_length as usize];
+// Every board size and box shape the size buttons step through, smallest first
+fn board_shapes() -> Vec<(i32, (i32, i32))> {
+    let mut shapes: Vec<(i32, (i32, i32))> = (MIN_SIDE_LENGTH..=MAX_SIDE_LENGTH)
+        .flat_map(|n| solver::box_shapes(n).into_iter().map(move |shape| (n, shape)))
+        .collect();
+    shapes.sort();
+    shapes
+}
 
-    let mut board = solver::SudokuBoard {values: empty_values, val_in_row: row_vals, val_in_col: col_vals, val_in_box: box_vals};
+// The region every square is in, which is the boxes unless jigsaw regions have been painted
+fn current_regions(regions: &Option<Vec<Vec<usize>>>, side_length: i32, box_shape: (i32, i32)) -> Vec<Vec<usize>> {
+    match regions {
+        Some(regions) => regions.clone(),
+        None => solver::box_regions(side_length, box_shape),
+    }
+}
+
+// The edges between squares in different regions, plus the outside border, each from one grid corner to the next
+fn region_borders(regions: &[Vec<usize>]) -> Vec<((usize, usize), (usize, usize))> {
+    let n = regions.len();
+    let mut segments = vec![((0, 0), (0, n)), ((n, 0), (n, n)), ((0, 0), (n, 0)), ((0, n), (n, n))];
+    for r in 0..n {
+        for c in 0..n {
+            if c + 1 < n && regions[r][c] != regions[r][c + 1] {
+                segments.push(((r, c + 1), (r + 1, c + 1)));
+            }
+            if r + 1 < n && regions[r][c] != regions[r + 1][c] {
+                segments.push(((r + 1, c), (r + 1, c + 1)));
+            }
+        }
+    }
+    segments
+}
+
+// A light color for each region, spread evenly around the color wheel
+fn region_color(region: usize, side_length: i32) -> conrod_core::Color {
+    conrod_core::color::hsl(region as f32 / side_length as f32 * std::f32::consts::TAU, 0.6, 0.8)
+}
+
+fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    format!("Time {}:{:02}:{:02}", secs / 3600, (secs / 60) % 60, secs % 60)
+}
+
+// Converts a puzzle read from a file into the strings shown in the GUI squares
+fn puzzle_to_strs(puzzle: &puzzle::Puzzle) -> Vec<Vec<String>> {
+    let side_length = puzzle.side_length;
+    (0..side_length as usize).map(|r| (0..side_length as usize).map(|c| match puzzle.value(r, c) {
+        Some(v) => puzzle.symbols.symbol(v),
+        None => "".to_string(),
+    }).collect()).collect()
+}
+
+fn puzzle_to_pencil_strs(puzzle: &puzzle::Puzzle) -> Vec<Vec<String>> {
+    puzzle.candidates.iter().map(|row| row.iter().map(|vals| puzzle.symbols.format_list(vals, puzzle.side_length)).collect()).collect()
+}
+
+// Collects what is on the GUI board into a puzzle that can be saved
+fn strs_to_puzzle(puzzle_strs: &[Vec<String>], given_cells: &[Vec<bool>], pencil_strs: &[Vec<String>], symbols: &SymbolSet, box_shape: (i32, i32), regions: &Option<Vec<Vec<usize>>>, constraints: &[puzzle::Constraint]) -> puzzle::Puzzle {
+    let side_length = puzzle_strs.len() as i32;
+    let mut puzzle = puzzle::Puzzle::new(side_length);
+    puzzle.box_shape = box_shape;
+    puzzle.regions = regions.clone();
+    puzzle.constraints = constraints.to_vec();
+    puzzle.symbols = symbols.clone();
+    for r in 0..side_length as usize {
+        for c in 0..side_length as usize {
+            let value = symbols.value(&puzzle_strs[r][c], side_length);
+            if given_cells[r][c] {
+                puzzle.givens[r][c] = value;
+            } else {
+                puzzle.entries[r][c] = value;
+            }
+            if value.is_none() {
+                puzzle.candidates[r][c] = symbols.parse_list(&pencil_strs[r][c], side_length);
+                puzzle.candidates[r][c].sort();
+            }
+        }
+    }
+    puzzle
+}
+
+// Loads the first puzzle in a file, as long as the GUI can show a puzzle of its size.
+// Digits read from a photo are only guesses, so they come in as entries for the player to check before setting the givens.
+fn load_puzzle(path: &Path) -> Result<puzzle::Puzzle, String> {
+    let puzzle = if photo::is_image(path) {
+        let read = photo::import_file(path)?;
+        puzzle::Puzzle { entries: read.givens.clone(), givens: puzzle::Puzzle::new(read.side_length).givens, ..read }
+    } else {
+        formats::load_file(path)?.into_iter().next().ok_or("The file does not contain any puzzles")?
+    };
+    puzzle.check()?;
+    if puzzle.side_length > MAX_SIDE_LENGTH || puzzle.side_length < MIN_SIDE_LENGTH {
+        return Err(format!("Unsupported size {}!", puzzle.side_length));
+    }
+    Ok(puzzle)
+}
+
+fn fill_solved_values(board_str: &mut [Vec<String>], symbols: &SymbolSet, side_length: i32, box_shape: (i32, i32), regions: Vec<Vec<usize>>, constraints: &[puzzle::Constraint], success_str: &mut String) {
+    if !solver::regions_are_valid(&regions, side_length) {
+        *success_str = format!("Every region needs {} squares", side_length);
+        return;
+    }
+    let mut board = solver::new_board(side_length, regions);
+    board.rules = puzzle::board_rules(constraints, side_length, box_shape);
 
     for r in 0..side_length {
         for c in 0..side_length {
             if board_str[r as usize][c as usize] != "".to_string() {
-                let result: Result<i32, _> = i32::from_str_radix(&board_str[r as usize][c as usize], (side_length + 1) as u32);
+                let result = symbols.value(&board_str[r as usize][c as usize], side_length);
                 match result {
-                    Err(_) => {*success_str = "Unable to solve!".to_string(); return;},
-                    Ok(num) => if num >= 1 && num <= side_length {if !solver::update_board(&mut board, num, r as usize, c as usize, side_length) {*success_str = "Unable to solve!".to_string(); return;}} else {*success_str = "Unable to solve!".to_string(); return;}
+                    Some(num) if num >= 1 && num <= side_length => if !solver::update_board(&mut board, num, r as usize, c as usize) {*success_str = "Unable to solve!".to_string(); return;},
+                    _ => {*success_str = "Unable to solve!".to_string(); return;}
                 }
             }
         }
@@ -73,7 +346,7 @@ fn fill_solved_values(board_str: &mut Vec<Vec<String>>, side_length: i32, succes
     if solver::solve_board(&mut board, side_length) {
         for r in 0..side_length {
             for c in 0..side_length {
-                board_str[r as usize][c as usize] = format_radix(board.values[r as usize][c as usize].unwrap() as u32, (side_length + 1) as u32).to_uppercase();//board[r as usize][c as usize][0].to_string();
+                board_str[r as usize][c as usize] = symbols.symbol(board.values[r as usize][c as usize].unwrap());//board[r as usize][c as usize][0].to_string();
             }
         }
         *success_str = "Solved!".to_string();
@@ -82,8 +355,55 @@ fn fill_solved_values(board_str: &mut Vec<Vec<String>>, side_length: i32, succes
     }
 }
 
+// The values typed into overlapping grids, as the givens of a puzzle (None if one of them isn't a value)
+fn multi_strs_to_puzzle(board_str: &[Vec<String>], layout: multigrid::Layout) -> Option<multigrid::MultiPuzzle> {
+    let symbols = SymbolSet::default_for(multigrid::GRID_SIZE as i32);
+    let mut puzzle = multigrid::MultiPuzzle::new(layout);
+    for (r, row) in board_str.iter().enumerate() {
+        for (c, s) in row.iter().enumerate() {
+            if !s.is_empty() {
+                puzzle.givens[r][c] = Some(symbols.value(s, multigrid::GRID_SIZE as i32)?);
+            }
+        }
+    }
+    Some(puzzle)
+}
+
+fn multi_puzzle_to_strs(puzzle: &multigrid::MultiPuzzle) -> Vec<Vec<String>> {
+    let symbols = SymbolSet::default_for(multigrid::GRID_SIZE as i32);
+    puzzle.givens.iter().map(|row| row.iter().map(|given| given.map_or("".to_string(), |v| symbols.symbol(v))).collect()).collect()
+}
+
+// Solves overlapping grids from the values typed into them, filling in the rest of every grid
+fn fill_multi_solved_values(board_str: &mut [Vec<String>], layout: multigrid::Layout, success_str: &mut String) {
+    let symbols = SymbolSet::default_for(multigrid::GRID_SIZE as i32);
+    let puzzle = match multi_strs_to_puzzle(board_str, layout) {
+        Some(puzzle) => puzzle,
+        None => {
+            *success_str = "Unable to solve!".to_string();
+            return;
+        }
+    };
+    match puzzle.solve() {
+        Some(values) => {
+            for (r, row) in values.iter().enumerate() {
+                for (c, value) in row.iter().enumerate() {
+                    board_str[r][c] = value.map_or("".to_string(), |v| symbols.symbol(v));
+                }
+            }
+            *success_str = "Solved!".to_string();
+        }
+        None => *success_str = "Unable to solve!".to_string(),
+    }
+}
+
 // code borrowed from Conrod hello world program https://docs.rs/conrod_core/latest/conrod_core/guide/chapter_3/index.html
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(code) = cli::run(&args) {
+        std::process::exit(code);
+    }
+
     // Build the window.
     let event_loop = glium::glutin::event_loop::EventLoop::new();
     let window = glium::glutin::window::WindowBuilder::new()
@@ -98,8 +418,8 @@ fn main() {
     let mut ui = conrod_core::UiBuilder::new([WIDTH as f64, HEIGHT as f64]).build();
 
     // Generate the widget identifiers.
-    widget_ids!(struct Ids { puzzle_board, reset_button, solve_button, success_text, sizeup_button, sizedown_button });
-    let ids = Ids::new(ui.widget_id_generator());
+    widget_ids!(struct Ids { puzzle_board, reset_button, solve_button, success_text, sizeup_button, sizedown_button, symbols_button, path_text, load_button, save_button, save_as_button, givens_button, pencil_button, timer_text, regions_button, paint_button, boxes_button, diagonals_button, windows_button, anti_knight_button, anti_king_button, non_consecutive_button, diagonal_down, diagonal_up, cage_button, cage_sum_text, clear_cages_button, thermo_button, clear_thermos_button, arrow_button, clear_arrows_button, marker_kind_button, marker_button, all_dots_button, all_xv_button, clear_markers_button, clue_kind_button, clue_sum_text, clue_button, clear_clues_button, line_kind_button, line_button, clear_lines_button, parity_kind_button, parity_button, clear_parity_button, rules_label, rules_box, rules_text, layout_button, region_lines[], cage_lines[], cage_sums[], thermo_lines[], thermo_bulbs[], arrow_lines[], edge_dots[], edge_holes[], edge_labels[], edge_strokes[], clue_squares[], clue_labels[], clue_arrows[], line_segments[], parity_marks[], grid_boards[], grid_lines[] });
+    let mut ids = Ids::new(ui.widget_id_generator());
 
     // Add a `Font` to the `Ui`'s `font::Map` from file.
     let assets = find_folder::Search::KidsThenParents(3, 5)
@@ -118,7 +438,37 @@ fn main() {
     // program variables
     let mut success_str: String = "".to_string();
     let mut side_length: i32 = 9;
+    let mut box_shape: (i32, i32) = (3, 3);
+    let mut regions: Option<Vec<Vec<usize>>> = None; // jigsaw regions replacing the boxes
+    let mut painting = false; // whether clicking a square puts it in paint_region
+    let mut paint_region: usize = 0;
+    let mut constraints: Vec<puzzle::Constraint> = Vec::new(); // extra rules, like the diagonals of Sudoku X
+    let mut drawing: Option<Drawing> = None; // what clicking a square adds it to (or takes it out of) drawn_cells for
+    let mut drawn_cells: Vec<(usize, usize)> = Vec::new(); // the squares of the cage, thermometer, arrow or marker being drawn, in the order clicked
+    let mut arrow_circle: Vec<(usize, usize)> = Vec::new(); // the circle of the arrow being drawn, once its shaft is being drawn
+    let mut edge_marker = puzzle::EdgeMarker::WhiteDot; // the marker put between squares
+    let mut line_kind = puzzle::LineKind::Whisper; // the kind of line drawn
+    let mut parity_even = false; // whether squares are marked even rather than odd
+    let mut layout: Option<multigrid::Layout> = None; // overlapping grids shown instead of the single board
+    let mut multi_strs: Vec<Vec<String>> = Vec::new(); // the values typed into the overlapping grids, on their whole canvas
+    let mut cage_sum_str: String = "".to_string();
+    let mut clue_step: Option<(i64, i64)> = None; // the kind of outside clue added, see clue_label
+    let mut clue_sum_str: String = "".to_string();
     let mut puzzle_strs: Vec<Vec<String>> = vec![vec!["".to_string(); side_length as usize]; side_length as usize];
+    let mut given_cells: Vec<Vec<bool>> = vec![vec![false; side_length as usize]; side_length as usize];
+    let mut pencil_strs: Vec<Vec<String>> = vec![vec!["".to_string(); side_length as usize]; side_length as usize];
+    let mut pencil_mode = false;
+    let mut symbols = SymbolSet::default_for(side_length);
+    let mut elapsed_before = Duration::ZERO; // time from before the current puzzle was loaded
+    let mut clock_start = Instant::now();
+    let mut shown_secs = 0;
+
+    let mut metadata = puzzle::Metadata::default(); // the title, rules text and so on of the loaded puzzle, kept when it is saved
+
+    // a puzzle file (a .sudoku save game, .variant puzzle, .sdk, .ss, .sdm, HoDoKu or pencil mark grid) can be passed on the command line to start with it loaded
+    let mut current_path: Option<PathBuf> = None;
+    let mut path_str: String = args.first().cloned().unwrap_or_else(|| "puzzle.sudoku".to_string());
+    let mut load_requested = !args.is_empty();
 
     // end program variables
 
@@ -151,9 +501,144 @@ fn main() {
 
         match &event {
             glium::glutin::event::Event::MainEventsCleared => {
+                // redraw once a second so the clock keeps ticking
+                let elapsed_secs = (elapsed_before + clock_start.elapsed()).as_secs();
+                if elapsed_secs != shown_secs {
+                    shown_secs = elapsed_secs;
+                    should_update_ui = true;
+                }
                 if should_update_ui {
                     should_update_ui = false;
 
+                    // one thick line wherever two neighbouring squares are in different regions, as (row, col) grid corners
+                    let region_map = current_regions(&regions, side_length, box_shape);
+                    let border_segments = region_borders(&region_map);
+                    ids.region_lines.resize(border_segments.len(), &mut ui.widget_id_generator());
+
+                    // the windows of Windoku are shaded
+                    let mut shaded = vec![vec![false; side_length as usize]; side_length as usize];
+                    if constraints.contains(&puzzle::Constraint::Windows) {
+                        for (r, c) in rules::window_units(side_length, box_shape).into_iter().flatten() {
+                            shaded[r][c] = true;
+                        }
+                    }
+
+                    // dashed outlines around the killer cages, in squares from the top left corner, with each cage's sum
+                    let mut cage_segments = Vec::new();
+                    let mut cage_labels = Vec::new();
+                    for constraint in &constraints {
+                        if let puzzle::Constraint::Cage { cells, sum } = constraint {
+                            for (from, to) in export::cage_outline(cells, 0.1) {
+                                cage_segments.extend(export::dashes(from, to, 0.08));
+                            }
+                            if let Some(&corner) = cells.iter().min() {
+                                cage_labels.push((sum.to_string(), corner));
+                            }
+                        }
+                    }
+                    ids.cage_lines.resize(cage_segments.len(), &mut ui.widget_id_generator());
+
+                    // thermometers as a line through the middle of their squares, with a bulb on the first one
+                    let mut thermo_segments = Vec::new();
+                    let mut thermo_bulbs = Vec::new();
+                    for constraint in &constraints {
+                        if let puzzle::Constraint::Thermometer { cells } = constraint {
+                            let center = |&(r, c): &(usize, usize)| (c as f64 + 0.5, r as f64 + 0.5);
+                            thermo_segments.extend(cells.windows(2).map(|pair| (center(&pair[0]), center(&pair[1]))));
+                            thermo_bulbs.extend(cells.first().map(center));
+                        }
+                    }
+                    ids.thermo_lines.resize(thermo_segments.len(), &mut ui.widget_id_generator());
+
+                    // odd squares get a circle and even squares a square, see through so that values still show
+                    let mut parity_marks = Vec::new();
+                    for constraint in &constraints {
+                        if let puzzle::Constraint::Parity { cell: (r, c), even } = constraint {
+                            parity_marks.push(((*c as f64 + 0.5, *r as f64 + 0.5), *even));
+                        }
+                    }
+                    ids.parity_marks.resize(parity_marks.len(), &mut ui.widget_id_generator());
+
+                    let mut line_segments = Vec::new();
+                    for constraint in &constraints {
+                        if let puzzle::Constraint::Line { kind, cells } = constraint {
+                            let center = |&(r, c): &(usize, usize)| (c as f64 + 0.5, r as f64 + 0.5);
+                            line_segments.extend(cells.windows(2).map(|pair| (center(&pair[0]), center(&pair[1]), *kind)));
+                        }
+                    }
+                    ids.line_segments.resize(line_segments.len(), &mut ui.widget_id_generator());
+                    ids.thermo_bulbs.resize(thermo_bulbs.len(), &mut ui.widget_id_generator());
+
+                    let mut arrow_segments = Vec::new();
+                    for constraint in &constraints {
+                        if let puzzle::Constraint::Arrow { circle, cells } = constraint {
+                            arrow_segments.extend(export::arrow_lines(circle, cells));
+                        }
+                    }
+                    ids.arrow_lines.resize(arrow_segments.len(), &mut ui.widget_id_generator());
+
+                    // dots sit on the edge between their squares, white ones with a hole, X and V are written there and
+                    // inequalities drawn as a < opening towards the larger square
+                    let mut edge_dots = Vec::new();
+                    let mut edge_holes = Vec::new();
+                    let mut edge_labels = Vec::new();
+                    let mut edge_strokes = Vec::new();
+                    for constraint in &constraints {
+                        if let puzzle::Constraint::Edge { marker, cells } = constraint {
+                            let at = export::edge_middle(*cells);
+                            match marker {
+                                puzzle::EdgeMarker::WhiteDot => {
+                                    edge_dots.push(at);
+                                    edge_holes.push(at);
+                                }
+                                puzzle::EdgeMarker::BlackDot => edge_dots.push(at),
+                                puzzle::EdgeMarker::X => edge_labels.push((at, "X")),
+                                puzzle::EdgeMarker::V => edge_labels.push((at, "V")),
+                                puzzle::EdgeMarker::Less => edge_strokes.extend(export::inequality_lines(*cells)),
+                            }
+                        }
+                    }
+                    ids.edge_dots.resize(edge_dots.len(), &mut ui.widget_id_generator());
+                    ids.edge_holes.resize(edge_holes.len(), &mut ui.widget_id_generator());
+                    ids.edge_labels.resize(edge_labels.len(), &mut ui.widget_id_generator());
+                    ids.edge_strokes.resize(edge_strokes.len(), &mut ui.widget_id_generator());
+                    ids.cage_sums.resize(cage_labels.len(), &mut ui.widget_id_generator());
+
+                    // clues outside the grid get a margin of one square around it, where the squares can be clicked
+                    // while drawing them
+                    let outside_clues = drawing == Some(Drawing::Clue) || constraints.iter().any(|c| c.outside_clue().is_some());
+                    let board_size = if outside_clues {WIDTH as f64 / 2.0 * side_length as f64 / (side_length + 2) as f64} else {WIDTH as f64 / 2.0};
+                    let mut clue_texts = Vec::new();
+                    let mut clue_arrow_segments = Vec::new();
+                    for constraint in &constraints {
+                        if let Some(clue) = constraint.outside_clue() {
+                            clue_texts.push((export::outside_clue_text(&clue), clue.sum.to_string()));
+                            clue_arrow_segments.extend(export::outside_clue_arrow(&clue));
+                        }
+                    }
+                    let n = side_length as i64;
+                    let clue_squares: Vec<(i64, i64)> = if drawing == Some(Drawing::Clue) {
+                        (-1..=n).flat_map(|r| (-1..=n).map(move |c| (r, c))).filter(|&(r, c)| r == -1 || r == n || c == -1 || c == n).collect()
+                    } else {
+                        Vec::new()
+                    };
+                    ids.clue_squares.resize(clue_squares.len(), &mut ui.widget_id_generator());
+                    ids.clue_labels.resize(clue_texts.len(), &mut ui.widget_id_generator());
+                    ids.clue_arrows.resize(clue_arrow_segments.len(), &mut ui.widget_id_generator());
+
+                    // the bold lines around every box of the overlapping grids, in squares from the top left of their canvas
+                    let mut grid_segments = Vec::new();
+                    for &(row, col) in layout.map_or(Vec::new(), |layout| layout.offsets()).iter() {
+                        let (top, left) = (row as f64, col as f64);
+                        for k in 0..=3 {
+                            let along = 3.0 * k as f64;
+                            grid_segments.push(((left, top + along), (left + 9.0, top + along)));
+                            grid_segments.push(((left + along, top), (left + along, top + 9.0)));
+                        }
+                    }
+                    ids.grid_boards.resize(layout.map_or(0, |layout| layout.offsets().len()), &mut ui.widget_id_generator());
+                    ids.grid_lines.resize(grid_segments.len(), &mut ui.widget_id_generator());
+
                     // Set the widgets.
                     let ui = &mut ui.set_widgets();
 
@@ -180,10 +665,28 @@ fn main() {
                         .w_h(WIDTH as f64 / 18.0, HEIGHT as f64 / 14.0)
                         .set(ids.sizeup_button, ui)
                     {
-                        success_str = "".to_string();
-                        let new_root_length = cmp::min(MAX_SIDE_LENGTH.root(), side_length.root() + 1);
-                        side_length = new_root_length * new_root_length;
+                        // the next larger shape, which may be the same size with differently shaped boxes
+                        if let Some((n, shape)) = board_shapes().into_iter().find(|s| *s > (side_length, box_shape)) {
+                            side_length = n;
+                            box_shape = shape;
+                        }
+                        success_str = format!("{}x{}, {}x{} boxes", side_length, side_length, box_shape.0, box_shape.1);
+                        if !symbols.supports(side_length) {
+                            symbols = SymbolSet::default_for(side_length);
+                        }
                         puzzle_strs = vec![vec!["".to_string(); side_length as usize]; side_length as usize];
+                        given_cells = vec![vec![false; side_length as usize]; side_length as usize];
+                        pencil_strs = vec![vec!["".to_string(); side_length as usize]; side_length as usize];
+                        regions = None;
+                        painting = false;
+                        paint_region = 0;
+                        constraints.retain(|c| c.cells().is_empty()); // cages, arrows, markers and the like don't fit the new board
+                        drawing = None;
+                        drawn_cells.clear();
+                        current_path = None;
+                        layout = None;
+                        multi_strs = Vec::new();
+                        metadata = puzzle::Metadata::default();
                     }
 
                     for _click in widget::Button::new()
@@ -192,10 +695,41 @@ fn main() {
                         .w_h(WIDTH as f64 / 18.0, HEIGHT as f64 / 14.0)
                         .set(ids.sizedown_button, ui)
                     {
-                        success_str = "".to_string();
-                        let new_root_length = cmp::max(MIN_SIDE_LENGTH.root(), side_length.root() - 1);
-                        side_length = new_root_length * new_root_length;
+                        if let Some((n, shape)) = board_shapes().into_iter().rev().find(|s| *s < (side_length, box_shape)) {
+                            side_length = n;
+                            box_shape = shape;
+                        }
+                        success_str = format!("{}x{}, {}x{} boxes", side_length, side_length, box_shape.0, box_shape.1);
+                        if !symbols.supports(side_length) {
+                            symbols = SymbolSet::default_for(side_length);
+                        }
                         puzzle_strs = vec![vec!["".to_string(); side_length as usize]; side_length as usize];
+                        given_cells = vec![vec![false; side_length as usize]; side_length as usize];
+                        pencil_strs = vec![vec!["".to_string(); side_length as usize]; side_length as usize];
+                        regions = None;
+                        painting = false;
+                        paint_region = 0;
+                        constraints.retain(|c| c.cells().is_empty()); // cages, arrows, markers and the like don't fit the new board
+                        drawing = None;
+                        drawn_cells.clear();
+                        current_path = None;
+                        layout = None;
+                        multi_strs = Vec::new();
+                        metadata = puzzle::Metadata::default();
+                    }
+
+                    for _click in widget::Button::new()
+                        .label(&symbols_label(&symbols))
+                        .right_from(ids.sizedown_button, 0.0)
+                        .w_h(WIDTH as f64 / 6.0, HEIGHT as f64 / 14.0)
+                        .set(ids.symbols_button, ui)
+                    {
+                        // the board keeps its values and pencil marks, only shown with the new symbols
+                        let mut puzzle = strs_to_puzzle(&puzzle_strs, &given_cells, &pencil_strs, &symbols, box_shape, &regions, &constraints);
+                        symbols = next_symbols(&symbols, side_length);
+                        puzzle.symbols = symbols.clone();
+                        puzzle_strs = puzzle_to_strs(&puzzle);
+                        pencil_strs = puzzle_to_pencil_strs(&puzzle);
                     }
 
                     for _click in widget::Button::new()
@@ -206,6 +740,18 @@ fn main() {
                     {
                         success_str = "".to_string();
                         puzzle_strs = vec![vec!["".to_string(); side_length as usize]; side_length as usize];
+                        given_cells = vec![vec![false; side_length as usize]; side_length as usize];
+                        pencil_strs = vec![vec!["".to_string(); side_length as usize]; side_length as usize];
+                        regions = None;
+                        painting = false;
+                        constraints.retain(|c| c.cells().is_empty());
+                        drawing = None;
+                        drawn_cells.clear();
+                        elapsed_before = Duration::ZERO;
+                        clock_start = Instant::now();
+                        layout = None;
+                        multi_strs = Vec::new();
+                        metadata = puzzle::Metadata::default();
                     }
 
                     for _click in widget::Button::new()
@@ -214,7 +760,10 @@ fn main() {
                         .w_h(WIDTH as f64 / 9.0, HEIGHT as f64 / 14.0)
                         .set(ids.solve_button, ui)
                     {
-                        fill_solved_values(&mut puzzle_strs, side_length, &mut success_str);
+                        match layout {
+                            Some(layout) => fill_multi_solved_values(&mut multi_strs, layout, &mut success_str),
+                            None => fill_solved_values(&mut puzzle_strs, &symbols, side_length, box_shape, region_map.clone(), &constraints, &mut success_str),
+                        }
                     }
 
                     widget::Text::new(&success_str)
@@ -224,25 +773,931 @@ fn main() {
                         .font_size(24)
                         .set(ids.success_text, ui);
 
+                    for result in widget::TextBox::new(&path_str)
+                        .top_left_of(ui.window)
+                        .w_h(WIDTH as f64 / 4.5, HEIGHT as f64 / 14.0)
+                        .font_size(18)
+                        .set(ids.path_text, ui)
+                    {
+                        match result {
+                            conrod_core::widget::text_box::Event::Enter => load_requested = true,
+                            conrod_core::widget::text_box::Event::Update(s) => path_str = s
+                        }
+                    }
+
+                    for _click in widget::Button::new()
+                        .label("Load")
+                        .down_from(ids.path_text, 0.0)
+                        .w_h(WIDTH as f64 / 13.5, HEIGHT as f64 / 14.0)
+                        .set(ids.load_button, ui)
+                    {
+                        load_requested = true;
+                    }
+
+                    // overlapping grids have files of their own, and take the place of the single board
+                    if load_requested && formats::is_multigrid_file(Path::new(&path_str)) {
+                        load_requested = false;
+                        match formats::load_multigrid_file(Path::new(&path_str)) {
+                            Ok(puzzle) => {
+                                layout = Some(puzzle.layout);
+                                multi_strs = multi_puzzle_to_strs(&puzzle);
+                                metadata = puzzle::Metadata::default(); // .multi files don't have any
+                                current_path = Some(PathBuf::from(&path_str));
+                                success_str = "Loaded!".to_string();
+                            }
+                            Err(e) => success_str = e,
+                        }
+                    }
+
+                    if load_requested {
+                        load_requested = false;
+                        match load_puzzle(Path::new(&path_str)) {
+                            Ok(puzzle) => {
+                                layout = None;
+                                multi_strs = Vec::new();
+                                side_length = puzzle.side_length;
+                                box_shape = puzzle.box_shape;
+                                regions = puzzle.regions.clone();
+                                constraints = puzzle.constraints.clone();
+                                metadata = puzzle.metadata.clone();
+                                painting = false;
+                                paint_region = 0;
+                                drawing = None;
+                                drawn_cells.clear();
+                                symbols = puzzle.symbols.clone();
+                                puzzle_strs = puzzle_to_strs(&puzzle);
+                                given_cells = puzzle.givens.iter().map(|row| row.iter().map(|g| g.is_some()).collect()).collect();
+                                pencil_strs = puzzle_to_pencil_strs(&puzzle);
+                                elapsed_before = Duration::from_secs(puzzle.elapsed_secs);
+                                clock_start = Instant::now();
+                                let loaded = PathBuf::from(&path_str);
+                                if formats::Format::from_extension(&loaded) == Some(formats::Format::SaveGame) {
+                                    current_path = Some(loaded);
+                                    success_str = "Loaded!".to_string();
+                                } else {
+                                    // only save games keep everything, so Save As offers one next to the file rather
+                                    // than overwriting a photo or a collection of puzzles, or losing the pencil marks
+                                    current_path = None;
+                                    path_str = loaded.with_extension("sudoku").display().to_string();
+                                    success_str = if photo::is_image(&loaded) {"Check it, then Set Givens"} else {"Loaded!"}.to_string();
+                                }
+                            }
+                            Err(e) => success_str = e,
+                        }
+                    }
+
+                    let mut save_path: Option<PathBuf> = None;
+                    for _click in widget::Button::new()
+                        .label("Save")
+                        .right_from(ids.load_button, 0.0)
+                        .w_h(WIDTH as f64 / 13.5, HEIGHT as f64 / 14.0)
+                        .set(ids.save_button, ui)
+                    {
+                        // a puzzle that did not come from a file it can be saved back to needs Save As
+                        match &current_path {
+                            Some(path) => save_path = Some(path.clone()),
+                            None => success_str = "Use Save As to pick a file".to_string(),
+                        }
+                    }
+
+                    for _click in widget::Button::new()
+                        .label("Save As")
+                        .right_from(ids.save_button, 0.0)
+                        .w_h(WIDTH as f64 / 13.5, HEIGHT as f64 / 14.0)
+                        .set(ids.save_as_button, ui)
+                    {
+                        save_path = Some(PathBuf::from(&path_str));
+                    }
+
+                    if let (Some(path), Some(layout)) = (&save_path, layout) {
+                        // everything typed into the grids is saved as givens
+                        let saved = match multi_strs_to_puzzle(&multi_strs, layout) {
+                            Some(puzzle) if formats::is_multigrid_file(path) => formats::save_multigrid_file(path, &puzzle),
+                            Some(_) => Err("Overlapping grids are saved as .multi files".to_string()),
+                            None => Err("Unable to save!".to_string()),
+                        };
+                        match saved {
+                            Ok(()) => {
+                                success_str = "Saved!".to_string();
+                                path_str = path.display().to_string();
+                                current_path = Some(path.clone());
+                            }
+                            Err(e) => success_str = e,
+                        }
+                    }
+
+                    if let Some(path) = save_path.as_ref().filter(|path| photo::is_image(path)) {
+                        success_str = format!("{} is a photo, save as .sudoku instead", path.display());
+                        save_path = None;
+                    }
+
+                    if let Some(path) = save_path.filter(|_| layout.is_none()) {
+                        let mut puzzle = strs_to_puzzle(&puzzle_strs, &given_cells, &pencil_strs, &symbols, box_shape, &regions, &constraints);
+                        puzzle.elapsed_secs = (elapsed_before + clock_start.elapsed()).as_secs();
+                        puzzle.metadata = metadata.clone();
+                        // .svg, .pdf and .tex files are printouts of the puzzle with its solution, everything else is a puzzle file
+                        let printable = path.extension().is_some_and(|e| ["svg", "pdf", "tex"].iter().any(|p| e.eq_ignore_ascii_case(p)));
+                        let saved = if printable {
+                            export::save_file(&path, &[puzzle], &export::PrintOptions { include_solutions: true, ..Default::default() })
+                        } else {
+                            formats::save_file(&path, &[puzzle])
+                        };
+                        match saved {
+                            Ok(()) => {
+                                success_str = "Saved!".to_string();
+                                path_str = path.display().to_string();
+                                // later Saves only go back to a save game, the other formats can't hold everything
+                                let save_game = formats::Format::from_extension(&path) == Some(formats::Format::SaveGame);
+                                current_path = Some(path).filter(|_| save_game);
+                            }
+                            Err(e) => success_str = e,
+                        }
+                    }
+
+                    for _click in widget::Button::new()
+                        .label("Set Givens")
+                        .down_from(ids.load_button, 0.0)
+                        .w_h(WIDTH as f64 / 9.0, HEIGHT as f64 / 14.0)
+                        .set(ids.givens_button, ui)
+                    {
+                        // everything filled in so far becomes part of the puzzle, and the clock starts over
+                        given_cells = puzzle_strs.iter().map(|row| row.iter().map(|s| !s.is_empty()).collect()).collect();
+                        elapsed_before = Duration::ZERO;
+                        clock_start = Instant::now();
+                    }
+
+                    for _click in widget::Button::new()
+                        .label(if pencil_mode {"Pencil: On"} else {"Pencil: Off"})
+                        .right_from(ids.givens_button, 0.0)
+                        .w_h(WIDTH as f64 / 9.0, HEIGHT as f64 / 14.0)
+                        .set(ids.pencil_button, ui)
+                    {
+                        pencil_mode = !pencil_mode;
+                    }
+
+                    widget::Text::new(&format_elapsed(elapsed_before + clock_start.elapsed()))
+                        .down_from(ids.givens_button, 0.0)
+                        .w_h(WIDTH as f64 / 4.5, HEIGHT as f64 / 14.0)
+                        .left_justify()
+                        .font_size(24)
+                        .set(ids.timer_text, ui);
+
+                    for _click in widget::Button::new()
+                        .label(if painting {"Done Painting"} else {"Paint Regions"})
+                        .down_from(ids.timer_text, 0.0)
+                        .w_h(WIDTH as f64 / 13.5, HEIGHT as f64 / 14.0)
+                        .set(ids.regions_button, ui)
+                    {
+                        if painting {
+                            painting = false;
+                            success_str = if solver::regions_are_valid(&region_map, side_length) {
+                                "Regions set!".to_string()
+                            } else {
+                                format!("Every region needs {} squares", side_length)
+                            };
+                        } else {
+                            // painting starts from the regions as they are, which are the boxes for a new jigsaw
+                            regions = Some(region_map.clone());
+                            painting = true;
+                            drawing = None;
+                            success_str = "Click squares to paint them".to_string();
+                        }
+                    }
+
+                    for _click in widget::Button::new()
+                        .label(&format!("Region {}", paint_region + 1))
+                        .right_from(ids.regions_button, 0.0)
+                        .w_h(WIDTH as f64 / 13.5, HEIGHT as f64 / 14.0)
+                        .color(region_color(paint_region, side_length))
+                        .set(ids.paint_button, ui)
+                    {
+                        paint_region = (paint_region + 1) % side_length as usize;
+                    }
+
+                    for _click in widget::Button::new()
+                        .label("Use Boxes")
+                        .right_from(ids.paint_button, 0.0)
+                        .w_h(WIDTH as f64 / 13.5, HEIGHT as f64 / 14.0)
+                        .set(ids.boxes_button, ui)
+                    {
+                        regions = None;
+                        painting = false;
+                    }
+
+                    let diagonals = constraints.contains(&puzzle::Constraint::Diagonals);
+                    for _click in widget::Button::new()
+                        .label(if diagonals {"Diagonals: On"} else {"Diagonals: Off"})
+                        .down_from(ids.regions_button, 0.0)
+                        .w_h(WIDTH as f64 / 9.0, HEIGHT as f64 / 14.0)
+                        .set(ids.diagonals_button, ui)
+                    {
+                        if diagonals {
+                            constraints.retain(|c| *c != puzzle::Constraint::Diagonals);
+                        } else {
+                            constraints.push(puzzle::Constraint::Diagonals);
+                        }
+                    }
+
+                    let windows = constraints.contains(&puzzle::Constraint::Windows);
+                    for _click in widget::Button::new()
+                        .label(if windows {"Windows: On"} else {"Windows: Off"})
+                        .right_from(ids.diagonals_button, 0.0)
+                        .w_h(WIDTH as f64 / 9.0, HEIGHT as f64 / 14.0)
+                        .set(ids.windows_button, ui)
+                    {
+                        if windows {
+                            constraints.retain(|c| *c != puzzle::Constraint::Windows);
+                        } else {
+                            constraints.push(puzzle::Constraint::Windows);
+                        }
+                    }
+
+                    let anti_knight = constraints.contains(&puzzle::Constraint::AntiKnight);
+                    for _click in widget::Button::new()
+                        .label(if anti_knight {"Anti-Knight: On"} else {"Anti-Knight: Off"})
+                        .down_from(ids.diagonals_button, 0.0)
+                        .w_h(WIDTH as f64 / 9.0, HEIGHT as f64 / 14.0)
+                        .set(ids.anti_knight_button, ui)
+                    {
+                        if anti_knight {
+                            constraints.retain(|c| *c != puzzle::Constraint::AntiKnight);
+                        } else {
+                            constraints.push(puzzle::Constraint::AntiKnight);
+                        }
+                    }
+
+                    let anti_king = constraints.contains(&puzzle::Constraint::AntiKing);
+                    for _click in widget::Button::new()
+                        .label(if anti_king {"Anti-King: On"} else {"Anti-King: Off"})
+                        .right_from(ids.anti_knight_button, 0.0)
+                        .w_h(WIDTH as f64 / 9.0, HEIGHT as f64 / 14.0)
+                        .set(ids.anti_king_button, ui)
+                    {
+                        if anti_king {
+                            constraints.retain(|c| *c != puzzle::Constraint::AntiKing);
+                        } else {
+                            constraints.push(puzzle::Constraint::AntiKing);
+                        }
+                    }
+
+                    // cages, thermometers and the like are drawn by clicking their squares, in the column right of the board
+                    for _click in widget::Button::new()
+                        .label(if drawing == Some(Drawing::Cage) {"Add Cage"} else {"Draw Cage"})
+                        .top_left_with_margins_on(ui.window, 0.0, WIDTH as f64 * 0.75)
+                        .w_h(WIDTH as f64 / 13.5, HEIGHT as f64 / 14.0)
+                        .set(ids.cage_button, ui)
+                    {
+                        if drawing != Some(Drawing::Cage) {
+                            drawing = Some(Drawing::Cage);
+                            painting = false;
+                            drawn_cells.clear();
+                            success_str = "Click squares, type the sum, then Add Cage".to_string();
+                        } else if drawn_cells.is_empty() {
+                            drawing = None;
+                            success_str = "".to_string();
+                        } else {
+                            match cage_sum_str.trim().parse::<i32>() {
+                                Ok(sum) if rules::sum_is_possible(drawn_cells.len(), sum, side_length) => {
+                                    drawn_cells.sort();
+                                    constraints.push(puzzle::Constraint::Cage { cells: drawn_cells.clone(), sum });
+                                    drawing = None;
+                                    drawn_cells.clear();
+                                    cage_sum_str = "".to_string();
+                                    success_str = "Cage added!".to_string();
+                                }
+                                Ok(sum) => success_str = format!("No {} different values add up to {}", drawn_cells.len(), sum),
+                                Err(_) => success_str = "Type the cage's sum first".to_string(),
+                            }
+                        }
+                    }
+
+                    for result in widget::TextBox::new(&cage_sum_str)
+                        .right_from(ids.cage_button, 0.0)
+                        .w_h(WIDTH as f64 / 13.5, HEIGHT as f64 / 14.0)
+                        .font_size(18)
+                        .set(ids.cage_sum_text, ui)
+                    {
+                        if let conrod_core::widget::text_box::Event::Update(s) = result {
+                            if s.chars().all(|ch| ch.is_ascii_digit()) {
+                                cage_sum_str = s;
+                            }
+                        }
+                    }
+
+                    for _click in widget::Button::new()
+                        .label("Clear Cages")
+                        .right_from(ids.cage_sum_text, 0.0)
+                        .w_h(WIDTH as f64 / 13.5, HEIGHT as f64 / 14.0)
+                        .set(ids.clear_cages_button, ui)
+                    {
+                        constraints.retain(|c| !matches!(c, puzzle::Constraint::Cage { .. }));
+                        drawing = None;
+                        drawn_cells.clear();
+                    }
+
+                    for _click in widget::Button::new()
+                        .label(if drawing == Some(Drawing::Thermometer) {"Add Thermo"} else {"Draw Thermo"})
+                        .down_from(ids.cage_button, 0.0)
+                        .w_h(WIDTH as f64 / 9.0, HEIGHT as f64 / 14.0)
+                        .set(ids.thermo_button, ui)
+                    {
+                        if drawing != Some(Drawing::Thermometer) {
+                            drawing = Some(Drawing::Thermometer);
+                            painting = false;
+                            drawn_cells.clear();
+                            success_str = "Click squares from the bulb up".to_string();
+                        } else if drawn_cells.is_empty() {
+                            drawing = None;
+                            success_str = "".to_string();
+                        } else if drawn_cells.len() < 2 {
+                            success_str = "A thermometer needs 2 or more squares".to_string();
+                        } else if !drawn_cells.windows(2).all(|pair| touching(pair[0], pair[1])) {
+                            success_str = "Each square must touch the one before".to_string();
+                        } else {
+                            constraints.push(puzzle::Constraint::Thermometer { cells: drawn_cells.clone() });
+                            drawing = None;
+                            drawn_cells.clear();
+                            success_str = "Thermometer added!".to_string();
+                        }
+                    }
+
+                    for _click in widget::Button::new()
+                        .label("Clear Thermos")
+                        .right_from(ids.thermo_button, 0.0)
+                        .w_h(WIDTH as f64 / 9.0, HEIGHT as f64 / 14.0)
+                        .set(ids.clear_thermos_button, ui)
+                    {
+                        constraints.retain(|c| !matches!(c, puzzle::Constraint::Thermometer { .. }));
+                        drawing = None;
+                        drawn_cells.clear();
+                    }
+
+                    // arrows take two steps, first the circle and then the shaft
+                    let arrow_label = match drawing {
+                        Some(Drawing::ArrowCircle) => "Draw Shaft",
+                        Some(Drawing::ArrowShaft) => "Add Arrow",
+                        _ => "Draw Arrow",
+                    };
+                    for _click in widget::Button::new()
+                        .label(arrow_label)
+                        .down_from(ids.thermo_button, 0.0)
+                        .w_h(WIDTH as f64 / 9.0, HEIGHT as f64 / 14.0)
+                        .set(ids.arrow_button, ui)
+                    {
+                        match drawing {
+                            Some(Drawing::ArrowCircle) => {
+                                if drawn_cells.is_empty() || !in_a_line(&drawn_cells) {
+                                    success_str = "The circle must be squares in a line".to_string();
+                                } else if drawn_cells.len() > 1 && side_length > 9 {
+                                    success_str = "Pills need single digit values (9x9 or smaller)".to_string();
+                                } else {
+                                    arrow_circle = drawn_cells.clone();
+                                    arrow_circle.sort();
+                                    drawn_cells.clear();
+                                    drawing = Some(Drawing::ArrowShaft);
+                                    success_str = "Click the shaft's squares in order".to_string();
+                                }
+                            }
+                            Some(Drawing::ArrowShaft) => {
+                                if drawn_cells.is_empty() {
+                                    success_str = "The arrow needs a shaft".to_string();
+                                } else if drawn_cells.iter().any(|cell| arrow_circle.contains(cell)) {
+                                    success_str = "The shaft can't go through the circle".to_string();
+                                } else if !arrow_circle.iter().any(|&cell| touching(cell, drawn_cells[0]))
+                                    || !drawn_cells.windows(2).all(|pair| touching(pair[0], pair[1])) {
+                                    success_str = "Each square must touch the one before".to_string();
+                                } else {
+                                    constraints.push(puzzle::Constraint::Arrow { circle: arrow_circle.clone(), cells: drawn_cells.clone() });
+                                    drawing = None;
+                                    drawn_cells.clear();
+                                    arrow_circle.clear();
+                                    success_str = "Arrow added!".to_string();
+                                }
+                            }
+                            _ => {
+                                drawing = Some(Drawing::ArrowCircle);
+                                painting = false;
+                                drawn_cells.clear();
+                                arrow_circle.clear();
+                                success_str = "Click the circle's squares".to_string();
+                            }
+                        }
+                    }
+
+                    for _click in widget::Button::new()
+                        .label("Clear Arrows")
+                        .right_from(ids.arrow_button, 0.0)
+                        .w_h(WIDTH as f64 / 9.0, HEIGHT as f64 / 14.0)
+                        .set(ids.clear_arrows_button, ui)
+                    {
+                        constraints.retain(|c| !matches!(c, puzzle::Constraint::Arrow { .. }));
+                        drawing = None;
+                        drawn_cells.clear();
+                        arrow_circle.clear();
+                    }
+
+                    for _click in widget::Button::new()
+                        .label(marker_label(edge_marker))
+                        .down_from(ids.arrow_button, 0.0)
+                        .w_h(WIDTH as f64 / 9.0, HEIGHT as f64 / 14.0)
+                        .set(ids.marker_kind_button, ui)
+                    {
+                        edge_marker = next_marker(edge_marker);
+                    }
+
+                    for _click in widget::Button::new()
+                        .label(if drawing == Some(Drawing::Edge) {"Done Markers"} else {"Draw Markers"})
+                        .right_from(ids.marker_kind_button, 0.0)
+                        .w_h(WIDTH as f64 / 9.0, HEIGHT as f64 / 14.0)
+                        .set(ids.marker_button, ui)
+                    {
+                        drawn_cells.clear();
+                        if drawing == Some(Drawing::Edge) {
+                            drawing = None;
+                            success_str = "".to_string();
+                        } else {
+                            drawing = Some(Drawing::Edge);
+                            painting = false;
+                            success_str = if edge_marker == puzzle::EdgeMarker::Less {
+                                "Click the smaller square, then the larger one next to it".to_string()
+                            } else {
+                                "Click two squares next to each other".to_string()
+                            };
+                        }
+                    }
+
+                    let all_dots = constraints.contains(&puzzle::Constraint::AllDotsGiven);
+                    for _click in widget::Button::new()
+                        .label(if all_dots {"All Dots: On"} else {"All Dots: Off"})
+                        .down_from(ids.marker_kind_button, 0.0)
+                        .w_h(WIDTH as f64 / 12.0, HEIGHT as f64 / 14.0)
+                        .set(ids.all_dots_button, ui)
+                    {
+                        if all_dots {
+                            constraints.retain(|c| *c != puzzle::Constraint::AllDotsGiven);
+                        } else {
+                            constraints.push(puzzle::Constraint::AllDotsGiven);
+                        }
+                    }
+
+                    let all_xv = constraints.contains(&puzzle::Constraint::AllXVGiven);
+                    for _click in widget::Button::new()
+                        .label(if all_xv {"All XV: On"} else {"All XV: Off"})
+                        .right_from(ids.all_dots_button, 0.0)
+                        .w_h(WIDTH as f64 / 12.0, HEIGHT as f64 / 14.0)
+                        .set(ids.all_xv_button, ui)
+                    {
+                        if all_xv {
+                            constraints.retain(|c| *c != puzzle::Constraint::AllXVGiven);
+                        } else {
+                            constraints.push(puzzle::Constraint::AllXVGiven);
+                        }
+                    }
+
+                    for _click in widget::Button::new()
+                        .label("Clear Markers")
+                        .right_from(ids.all_xv_button, 0.0)
+                        .w_h(WIDTH as f64 / 12.0, HEIGHT as f64 / 14.0)
+                        .set(ids.clear_markers_button, ui)
+                    {
+                        constraints.retain(|c| !matches!(c, puzzle::Constraint::Edge { .. }));
+                        if drawing == Some(Drawing::Edge) {
+                            drawn_cells.clear();
+                        }
+                    }
+
+                    // sandwich and little killer clues are typed in, then put around the board by clicking there
+                    for _click in widget::Button::new()
+                        .label(clue_label(clue_step))
+                        .down_from(ids.all_dots_button, 0.0)
+                        .w_h(WIDTH as f64 / 6.0, HEIGHT as f64 / 14.0)
+                        .set(ids.clue_kind_button, ui)
+                    {
+                        clue_step = next_clue(clue_step);
+                    }
+
+                    for result in widget::TextBox::new(&clue_sum_str)
+                        .right_from(ids.clue_kind_button, 0.0)
+                        .w_h(WIDTH as f64 / 18.0, HEIGHT as f64 / 14.0)
+                        .font_size(18)
+                        .set(ids.clue_sum_text, ui)
+                    {
+                        if let conrod_core::widget::text_box::Event::Update(s) = result {
+                            if s.chars().all(|ch| ch.is_ascii_digit()) {
+                                clue_sum_str = s;
+                            }
+                        }
+                    }
+
+                    for _click in widget::Button::new()
+                        .label(if drawing == Some(Drawing::Clue) {"Done Clues"} else {"Draw Clues"})
+                        .down_from(ids.clue_kind_button, 0.0)
+                        .w_h(WIDTH as f64 / 9.0, HEIGHT as f64 / 14.0)
+                        .set(ids.clue_button, ui)
+                    {
+                        drawn_cells.clear();
+                        if drawing == Some(Drawing::Clue) {
+                            drawing = None;
+                            success_str = "".to_string();
+                        } else {
+                            drawing = Some(Drawing::Clue);
+                            painting = false;
+                            success_str = "Type a clue, then click where it goes around the board".to_string();
+                        }
+                    }
+
+                    for _click in widget::Button::new()
+                        .label("Clear Clues")
+                        .right_from(ids.clue_button, 0.0)
+                        .w_h(WIDTH as f64 / 9.0, HEIGHT as f64 / 14.0)
+                        .set(ids.clear_clues_button, ui)
+                    {
+                        constraints.retain(|c| c.outside_clue().is_none());
+                    }
+
+                    // whisper, renban and palindrome lines are drawn square by square, like thermometers
+                    for _click in widget::Button::new()
+                        .label(line_label(line_kind))
+                        .down_from(ids.clue_button, 0.0)
+                        .w_h(WIDTH as f64 / 12.0, HEIGHT as f64 / 14.0)
+                        .set(ids.line_kind_button, ui)
+                    {
+                        line_kind = next_line(line_kind);
+                    }
+
+                    for _click in widget::Button::new()
+                        .label(if drawing == Some(Drawing::Line) {"Add Line"} else {"Draw Line"})
+                        .right_from(ids.line_kind_button, 0.0)
+                        .w_h(WIDTH as f64 / 13.5, HEIGHT as f64 / 14.0)
+                        .set(ids.line_button, ui)
+                    {
+                        if drawing != Some(Drawing::Line) {
+                            drawing = Some(Drawing::Line);
+                            painting = false;
+                            drawn_cells.clear();
+                            success_str = "Click squares along the line".to_string();
+                        } else if drawn_cells.is_empty() {
+                            drawing = None;
+                            success_str = "".to_string();
+                        } else if drawn_cells.len() < 2 {
+                            success_str = "A line needs 2 or more squares".to_string();
+                        } else if !drawn_cells.windows(2).all(|pair| touching(pair[0], pair[1])) {
+                            success_str = "Each square must touch the one before".to_string();
+                        } else {
+                            constraints.push(puzzle::Constraint::Line { kind: line_kind, cells: drawn_cells.clone() });
+                            drawing = None;
+                            drawn_cells.clear();
+                            success_str = "Line added!".to_string();
+                        }
+                    }
+
+                    for _click in widget::Button::new()
+                        .label("Clear Lines")
+                        .right_from(ids.line_button, 0.0)
+                        .w_h(WIDTH as f64 / 13.5, HEIGHT as f64 / 14.0)
+                        .set(ids.clear_lines_button, ui)
+                    {
+                        constraints.retain(|c| !matches!(c, puzzle::Constraint::Line { .. }));
+                        drawing = None;
+                        drawn_cells.clear();
+                    }
+
+                    for _click in widget::Button::new()
+                        .label(if parity_even {"Parity: Even"} else {"Parity: Odd"})
+                        .down_from(ids.line_kind_button, 0.0)
+                        .w_h(WIDTH as f64 / 12.0, HEIGHT as f64 / 14.0)
+                        .set(ids.parity_kind_button, ui)
+                    {
+                        parity_even = !parity_even;
+                    }
+
+                    for _click in widget::Button::new()
+                        .label(if drawing == Some(Drawing::Parity) {"Done Parity"} else {"Draw Parity"})
+                        .right_from(ids.parity_kind_button, 0.0)
+                        .w_h(WIDTH as f64 / 13.5, HEIGHT as f64 / 14.0)
+                        .set(ids.parity_button, ui)
+                    {
+                        drawn_cells.clear();
+                        if drawing == Some(Drawing::Parity) {
+                            drawing = None;
+                            success_str = "".to_string();
+                        } else {
+                            drawing = Some(Drawing::Parity);
+                            painting = false;
+                            success_str = "Click the squares to mark".to_string();
+                        }
+                    }
+
+                    for _click in widget::Button::new()
+                        .label("Clear Parity")
+                        .right_from(ids.parity_button, 0.0)
+                        .w_h(WIDTH as f64 / 13.5, HEIGHT as f64 / 14.0)
+                        .set(ids.clear_parity_button, ui)
+                    {
+                        constraints.retain(|c| !matches!(c, puzzle::Constraint::Parity { .. }));
+                    }
 
-                    let mut nums = widget::Matrix::new(side_length as usize, side_length as usize)
-                        .mid_bottom_of(ui.window)
-                        .w_h(WIDTH as f64 / 2.0, WIDTH as f64 / 2.0)
-                        .set(ids.puzzle_board, ui);
-                    while let Some(num) = nums.next(ui) {
-                        let (r, c) = (num.row, num.col);
-                        if r < side_length as usize && c < side_length as usize { // for some reason the number of columns doesn't decrease dynamically, but the number of rows does
-                            let square = widget::TextBox::new(&puzzle_strs[r][c]).font_size(32).center_justify();
-                            for result in num.set(square, ui) {
-                                match result {
-                                    conrod_core::widget::text_box::Event::Enter => puzzle_strs[r][c] = "".to_string(),
-                                    conrod_core::widget::text_box::Event::Update(s) => puzzle_strs[r][c] = update_square_str(s, side_length)
+                    // the rules of a variant, written out for whoever the puzzle is shared with
+                    widget::Text::new("Rules:")
+                        .down_from(ids.parity_kind_button, 0.0)
+                        .w_h(WIDTH as f64 / 4.0, HEIGHT as f64 / 28.0)
+                        .left_justify()
+                        .font_size(18)
+                        .set(ids.rules_label, ui);
+
+                    widget::BorderedRectangle::new([WIDTH as f64 / 4.0, HEIGHT as f64 / 14.0 * 4.0])
+                        .down_from(ids.rules_label, 0.0)
+                        .color(conrod_core::color::WHITE)
+                        .set(ids.rules_box, ui);
+
+                    let rules_str = metadata.rules.clone().unwrap_or_default();
+                    if let Some(edited) = widget::TextEdit::new(&rules_str)
+                        .padded_wh_of(ids.rules_box, 5.0)
+                        .mid_top_with_margin_on(ids.rules_box, 5.0)
+                        .font_size(16)
+                        .left_justify()
+                        .wrap_by_whitespace()
+                        .color(conrod_core::color::BLACK)
+                        .set(ids.rules_text, ui)
+                    {
+                        metadata.rules = if edited.trim().is_empty() {None} else {Some(edited)};
+                    }
+
+                    let non_consecutive = constraints.contains(&puzzle::Constraint::NonConsecutive);
+                    for _click in widget::Button::new()
+                        .label(if non_consecutive {"Non-Consecutive: On"} else {"Non-Consecutive: Off"})
+                        .down_from(ids.anti_knight_button, 0.0)
+                        .w_h(WIDTH as f64 / 4.5, HEIGHT as f64 / 14.0)
+                        .set(ids.non_consecutive_button, ui)
+                    {
+                        if non_consecutive {
+                            constraints.retain(|c| *c != puzzle::Constraint::NonConsecutive);
+                        } else {
+                            constraints.push(puzzle::Constraint::NonConsecutive);
+                        }
+                    }
+
+                    // Samurai and the other overlapping grids take the single board's place until switched back
+                    let layout_label = match layout {
+                        None => "Grids: Single",
+                        Some(multigrid::Layout::Samurai) => "Grids: Samurai",
+                        Some(multigrid::Layout::Butterfly) => "Grids: Butterfly",
+                        Some(multigrid::Layout::Twin) => "Grids: Twin",
+                    };
+                    for _click in widget::Button::new()
+                        .label(layout_label)
+                        .down_from(ids.non_consecutive_button, 0.0)
+                        .w_h(WIDTH as f64 / 4.5, HEIGHT as f64 / 14.0)
+                        .set(ids.layout_button, ui)
+                    {
+                        layout = match layout {
+                            None => Some(multigrid::Layout::Samurai),
+                            Some(multigrid::Layout::Samurai) => Some(multigrid::Layout::Butterfly),
+                            Some(multigrid::Layout::Butterfly) => Some(multigrid::Layout::Twin),
+                            Some(multigrid::Layout::Twin) => None,
+                        };
+                        let canvas = layout.map_or(0, |layout| layout.canvas_size());
+                        multi_strs = vec![vec!["".to_string(); canvas]; canvas];
+                        drawing = None;
+                        drawn_cells.clear();
+                        painting = false;
+                        success_str = "".to_string();
+                    }
+
+
+                    if let Some(layout) = layout {
+                        // every grid is a matrix of its own, on a canvas taking the single board's place. Squares where
+                        // grids overlap are in more than one matrix, all editing the same text.
+                        let square = WIDTH as f64 / 2.0 / layout.canvas_size() as f64;
+                        let (left, top) = (-(WIDTH as f64) / 4.0, -(HEIGHT as f64) / 2.0 + WIDTH as f64 / 2.0);
+                        let to_screen = |(x, y): (f64, f64)| [left + x * square, top - y * square];
+                        let value_font = cmp::min(32, (square * 0.5) as u32);
+                        for (i, &(row, col)) in layout.offsets().iter().enumerate() {
+                            let size = multigrid::GRID_SIZE as f64 * square;
+                            let mut nums = widget::Matrix::new(multigrid::GRID_SIZE, multigrid::GRID_SIZE)
+                                .x_y(left + col as f64 * square + size / 2.0, top - row as f64 * square - size / 2.0)
+                                .w_h(size, size)
+                                .set(ids.grid_boards[i], ui);
+                            while let Some(num) = nums.next(ui) {
+                                let (r, c) = (row + num.row, col + num.col);
+                                let square = widget::TextBox::new(&multi_strs[r][c])
+                                    .font_size(value_font)
+                                    .center_justify()
+                                    .text_color(conrod_core::color::BLACK)
+                                    .color(conrod_core::color::WHITE);
+                                for result in num.set(square, ui) {
+                                    match result {
+                                        conrod_core::widget::text_box::Event::Enter => multi_strs[r][c] = "".to_string(),
+                                        conrod_core::widget::text_box::Event::Update(s) => {
+                                            multi_strs[r][c] = update_square_str(s, &SymbolSet::default_for(multigrid::GRID_SIZE as i32), multigrid::GRID_SIZE as i32)
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        for (i, (from, to)) in grid_segments.iter().enumerate() {
+                            widget::Line::abs(to_screen(*from), to_screen(*to))
+                                .thickness(3.0)
+                                .color(conrod_core::color::BLACK)
+                                .set(ids.grid_lines[i], ui);
+                        }
+                    } else {
+                        let mut nums = widget::Matrix::new(side_length as usize, side_length as usize)
+                            .mid_bottom_with_margin_on(ui.window, (WIDTH as f64 / 2.0 - board_size) / 2.0)
+                            .w_h(board_size, board_size)
+                            .set(ids.puzzle_board, ui);
+                        // smaller text for larger puzzles, so that every symbol still fits its square
+                        let square_size = board_size / side_length as f64;
+                        let value_font = cmp::min(32, (square_size * 0.5) as u32);
+                        let pencil_font = cmp::min(14, (square_size * 0.25) as u32).max(6);
+                        while let Some(num) = nums.next(ui) {
+                            let (r, c) = (num.row, num.col);
+                            if r < side_length as usize && c < side_length as usize { // for some reason the number of columns doesn't decrease dynamically, but the number of rows does
+                                if painting {
+                                    let region = region_map[r][c];
+                                    let label = (region + 1).to_string();
+                                    let square = widget::Button::new().label(&label).label_font_size(value_font / 2 + 4).color(region_color(region, side_length));
+                                    for _click in num.set(square, ui) {
+                                        if let Some(regions) = regions.as_mut() {
+                                            regions[r][c] = paint_region;
+                                        }
+                                    }
+                                    continue;
+                                }
+                                if drawing.is_some() {
+                                    let selected = drawn_cells.contains(&(r, c));
+                                    let color = if selected {
+                                        conrod_core::color::LIGHT_ORANGE
+                                    } else if drawing == Some(Drawing::ArrowShaft) && arrow_circle.contains(&(r, c)) {
+                                        conrod_core::color::LIGHT_YELLOW
+                                    } else if shaded[r][c] {
+                                        SHADE_COLOR
+                                    } else {
+                                        conrod_core::color::WHITE
+                                    };
+                                    let square = widget::Button::new()
+                                        .label(&puzzle_strs[r][c])
+                                        .label_font_size(value_font)
+                                        .color(color);
+                                    for _click in num.set(square, ui) {
+                                        if drawing == Some(Drawing::Clue) {
+                                            success_str = "Click the squares around the board".to_string();
+                                            continue;
+                                        }
+                                        if drawing == Some(Drawing::Parity) {
+                                            toggle_parity(&mut constraints, (r, c), parity_even);
+                                            continue;
+                                        }
+                                        if selected {
+                                            drawn_cells.retain(|&cell| cell != (r, c));
+                                        } else {
+                                            drawn_cells.push((r, c));
+                                        }
+                                        if drawing == Some(Drawing::Edge) && drawn_cells.len() == 2 {
+                                            let (a, b) = (drawn_cells[0], drawn_cells[1]);
+                                            if a.0.abs_diff(b.0) + a.1.abs_diff(b.1) != 1 {
+                                                success_str = "The squares must be next to each other".to_string();
+                                            } else if edge_marker == puzzle::EdgeMarker::Less && region_map[a.0][a.1] != region_map[b.0][b.1] {
+                                                success_str = "Inequality signs must be inside a box".to_string();
+                                            } else {
+                                                toggle_edge(&mut constraints, edge_marker, a, b);
+                                            }
+                                            drawn_cells.clear();
+                                        }
+                                    }
+                                    continue;
+                                }
+                                // givens can't be changed, and in pencil mode the empty squares show and edit their pencil marks instead
+                                let editing_pencil = pencil_mode && puzzle_strs[r][c].is_empty();
+                                let background = if shaded[r][c] {SHADE_COLOR} else {conrod_core::color::WHITE};
+                                let square = if editing_pencil {
+                                    widget::TextBox::new(&pencil_strs[r][c]).font_size(pencil_font).center_justify().text_color(conrod_core::color::DARK_GREY).color(background)
+                                } else {
+                                    let color = if given_cells[r][c] {conrod_core::color::BLACK} else {conrod_core::color::BLUE};
+                                    widget::TextBox::new(&puzzle_strs[r][c]).font_size(value_font).center_justify().text_color(color).color(background)
+                                };
+                                for result in num.set(square, ui) {
+                                    if given_cells[r][c] {
+                                        continue;
+                                    }
+                                    match result {
+                                        conrod_core::widget::text_box::Event::Enter if editing_pencil => pencil_strs[r][c] = "".to_string(),
+                                        conrod_core::widget::text_box::Event::Update(s) if editing_pencil => pencil_strs[r][c] = update_pencil_str(s, &symbols, side_length),
+                                        conrod_core::widget::text_box::Event::Enter => puzzle_strs[r][c] = "".to_string(),
+                                        conrod_core::widget::text_box::Event::Update(s) => puzzle_strs[r][c] = update_square_str(s, &symbols, side_length)
+                                    }
+                                }
+                            }
+
+                        }
+
+                        if let Some(rect) = ui.rect_of(ids.puzzle_board) {
+                            if diagonals {
+                                let line_color = conrod_core::color::LIGHT_BLUE;
+                                widget::Line::abs([rect.left(), rect.top()], [rect.right(), rect.bottom()]).thickness(2.0).color(line_color).set(ids.diagonal_down, ui);
+                                widget::Line::abs([rect.left(), rect.bottom()], [rect.right(), rect.top()]).thickness(2.0).color(line_color).set(ids.diagonal_up, ui);
+                            }
+                            let square = rect.w() / side_length as f64;
+                            let point = |(row, col): (usize, usize)| [rect.left() + col as f64 * square, rect.top() - row as f64 * square];
+                            for (i, (from, to)) in border_segments.iter().enumerate() {
+                                widget::Line::abs(point(*from), point(*to))
+                                    .thickness(3.0)
+                                    .color(conrod_core::color::BLACK)
+                                    .set(ids.region_lines[i], ui);
+                            }
+                            let to_screen = |(x, y): (f64, f64)| [rect.left() + x * square, rect.top() - y * square];
+                            for (i, (at, even)) in parity_marks.iter().enumerate() {
+                                let [x, y] = to_screen(*at);
+                                if *even {
+                                    widget::Rectangle::fill([square * 0.76, square * 0.76]).x_y(x, y).color(PARITY_COLOR).set(ids.parity_marks[i], ui);
+                                } else {
+                                    widget::Circle::fill(square * 0.4).x_y(x, y).color(PARITY_COLOR).set(ids.parity_marks[i], ui);
+                                }
+                            }
+                            for (i, (from, to, kind)) in line_segments.iter().enumerate() {
+                                widget::Line::abs(to_screen(*from), to_screen(*to))
+                                    .thickness(square * 0.15)
+                                    .color(line_color(*kind))
+                                    .set(ids.line_segments[i], ui);
+                            }
+                            for (i, (from, to)) in thermo_segments.iter().enumerate() {
+                                widget::Line::abs(to_screen(*from), to_screen(*to))
+                                    .thickness(square * 0.25)
+                                    .color(THERMOMETER_COLOR)
+                                    .set(ids.thermo_lines[i], ui);
+                            }
+                            for (i, at) in thermo_bulbs.iter().enumerate() {
+                                let [x, y] = to_screen(*at);
+                                widget::Circle::fill(square * 0.38)
+                                    .x_y(x, y)
+                                    .color(THERMOMETER_COLOR)
+                                    .set(ids.thermo_bulbs[i], ui);
+                            }
+                            for (i, (from, to)) in cage_segments.iter().enumerate() {
+                                widget::Line::abs(to_screen(*from), to_screen(*to))
+                                    .thickness(1.0)
+                                    .color(conrod_core::color::BLACK)
+                                    .set(ids.cage_lines[i], ui);
+                            }
+                            for (i, (from, to)) in arrow_segments.iter().enumerate() {
+                                widget::Line::abs(to_screen(*from), to_screen(*to))
+                                    .thickness(2.0)
+                                    .color(conrod_core::color::DARK_GREY)
+                                    .set(ids.arrow_lines[i], ui);
+                            }
+                            for (i, at) in edge_dots.iter().enumerate() {
+                                let [x, y] = to_screen(*at);
+                                widget::Circle::fill(square * 0.12).x_y(x, y).color(conrod_core::color::BLACK).set(ids.edge_dots[i], ui);
+                            }
+                            for (i, at) in edge_holes.iter().enumerate() {
+                                let [x, y] = to_screen(*at);
+                                widget::Circle::fill(square * 0.12 - 1.5).x_y(x, y).color(conrod_core::color::WHITE).set(ids.edge_holes[i], ui);
+                            }
+                            let marker_font = cmp::max(8, (square * 0.3) as u32);
+                            for (i, (at, label)) in edge_labels.iter().enumerate() {
+                                let [x, y] = to_screen(*at);
+                                widget::Text::new(label).x_y(x, y).font_size(marker_font).color(conrod_core::color::BLACK).set(ids.edge_labels[i], ui);
+                            }
+                            for (i, (from, to)) in edge_strokes.iter().enumerate() {
+                                widget::Line::abs(to_screen(*from), to_screen(*to))
+                                    .thickness(2.0)
+                                    .color(conrod_core::color::BLACK)
+                                    .set(ids.edge_strokes[i], ui);
+                            }
+                            let sum_font = cmp::max(8, (square * 0.22) as u32);
+                            for (i, (label, (r, c))) in cage_labels.iter().enumerate() {
+                                widget::Text::new(label)
+                                    .x_y(rect.left() + (*c as f64 + 0.27) * square, rect.top() - (*r as f64 + 0.27) * square)
+                                    .font_size(sum_font)
+                                    .color(conrod_core::color::BLACK)
+                                    .set(ids.cage_sums[i], ui);
+                            }
+                            for (i, &(r, c)) in clue_squares.iter().enumerate() {
+                                let [x, y] = to_screen((c as f64 + 0.5, r as f64 + 0.5));
+                                for _click in widget::Button::new()
+                                    .x_y(x, y)
+                                    .w_h(square - 2.0, square - 2.0)
+                                    .color(conrod_core::color::LIGHT_GREY)
+                                    .set(ids.clue_squares[i], ui)
+                                {
+                                    match toggle_clue(&mut constraints, side_length, (r, c), clue_step, &clue_sum_str) {
+                                        Ok(message) | Err(message) => success_str = message,
+                                    }
                                 }
                             }
+                            let clue_font = cmp::max(8, (square * 0.35) as u32);
+                            for (i, (at, label)) in clue_texts.iter().enumerate() {
+                                let [x, y] = to_screen(*at);
+                                widget::Text::new(label).x_y(x, y).font_size(clue_font).color(conrod_core::color::BLACK).set(ids.clue_labels[i], ui);
+                            }
+                            for (i, (from, to)) in clue_arrow_segments.iter().enumerate() {
+                                widget::Line::abs(to_screen(*from), to_screen(*to))
+                                    .thickness(1.0)
+                                    .color(conrod_core::color::BLACK)
+                                    .set(ids.clue_arrows[i], ui);
+                            }
                         }
-                        
                     }
-                    
 
                     // end setting widgets
 
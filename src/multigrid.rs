@@ -0,0 +1,332 @@
+// Puzzles made of several 9x9 grids that overlap, like the five grids of Samurai sudoku
+//
+// The grids sit at offsets on a larger square canvas, and each one is solved as an ordinary solver::SudokuBoard.
+// A square where grids overlap is in all of them, so a value placed there has to fit the rows, columns and boxes of
+// every one of them.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use rand::seq::SliceRandom;
+
+use crate::solver::{self, Forced, SudokuBoard};
+
+pub const GRID_SIZE: usize = 9;
+pub const BOX_SHAPE: (i32, i32) = (3, 3);
+const MAX_RESTARTS: usize = 10; // after this many the givens are taken to have no solution, rather than searching forever
+
+// The ways of putting grids together
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Layout {
+    Samurai, // four grids around a middle one, each sharing a corner box with it
+    Butterfly, // four grids in a 12x12 square, each overlapping the others by two thirds
+    Twin, // two grids sharing a corner box
+}
+
+impl Layout {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layout::Samurai => "samurai",
+            Layout::Butterfly => "butterfly",
+            Layout::Twin => "twin",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Layout> {
+        [Layout::Samurai, Layout::Butterfly, Layout::Twin].into_iter().find(|layout| layout.name() == name)
+    }
+
+    // The (row, col) of the top left square of each grid on the canvas
+    pub fn offsets(&self) -> Vec<(usize, usize)> {
+        match self {
+            Layout::Samurai => vec![(0, 0), (0, 12), (6, 6), (12, 0), (12, 12)],
+            Layout::Butterfly => vec![(0, 0), (0, 3), (3, 0), (3, 3)],
+            Layout::Twin => vec![(0, 0), (6, 6)],
+        }
+    }
+
+    // The width and height of the canvas, in squares
+    pub fn canvas_size(&self) -> usize {
+        self.offsets().iter().map(|&(r, c)| r.max(c)).max().unwrap_or(0) + GRID_SIZE
+    }
+
+    // The grids a square of the canvas is in, with its (row, col) inside each of them
+    pub fn grids_at(&self, row: usize, col: usize) -> Vec<(usize, (usize, usize))> {
+        self.offsets().iter().enumerate()
+            .filter(|(_, &(r, c))| row >= r && row < r + GRID_SIZE && col >= c && col < c + GRID_SIZE)
+            .map(|(i, &(r, c))| (i, (row - r, col - c)))
+            .collect()
+    }
+
+    // Whether a square of the canvas is in any grid, rather than in a gap between them
+    pub fn is_used(&self, row: usize, col: usize) -> bool {
+        !self.grids_at(row, col).is_empty()
+    }
+}
+
+// A puzzle of overlapping grids, its givens laid out on the whole canvas (None in the gaps between grids too)
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MultiPuzzle {
+    pub layout: Layout,
+    pub givens: Vec<Vec<Option<i32>>>,
+}
+
+impl MultiPuzzle {
+    pub fn new(layout: Layout) -> MultiPuzzle {
+        let n = layout.canvas_size();
+        MultiPuzzle { layout, givens: vec![vec![None; n]; n] }
+    }
+
+    // Checks that the givens cover the canvas, with values from 1 to 9 and only in squares that are part of a grid
+    pub fn check(&self) -> Result<(), String> {
+        let n = self.layout.canvas_size();
+        if self.givens.len() != n || self.givens.iter().any(|row| row.len() != n) {
+            return Err(format!("The givens of a {} puzzle must cover its {}x{} canvas", self.layout.name(), n, n));
+        }
+        for (row, values) in self.givens.iter().enumerate() {
+            for (col, value) in values.iter().enumerate() {
+                match value {
+                    Some(_) if !self.layout.is_used(row, col) => return Err(format!("Row {} column {} is not in any grid", row + 1, col + 1)),
+                    Some(value) if *value < 1 || *value > GRID_SIZE as i32 => return Err(format!("{} is not a value from 1 to {}", value, GRID_SIZE)),
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Solves every grid at once, returning the values of the whole canvas, or None if the givens can't be completed
+    pub fn solve(&self) -> Option<Vec<Vec<Option<i32>>>> {
+        self.check().ok()?;
+        let n = self.layout.canvas_size();
+        let regions = solver::box_regions(GRID_SIZE as i32, BOX_SHAPE);
+        let grids: Vec<SudokuBoard> = self.layout.offsets().iter().map(|_| solver::new_board(GRID_SIZE as i32, regions.clone())).collect();
+        let shared = (0..n).flat_map(|r| (0..n).map(move |c| self.layout.grids_at(r, c))).filter(|grids| grids.len() > 1).collect();
+        let mut board = MultiBoard { layout: self.layout, units: solver::full_units(&grids[0], GRID_SIZE as i32), grids, shared, guesses_left: 0 };
+        for row in 0..n {
+            for col in 0..n {
+                if let Some(value) = self.givens[row][col] {
+                    if !board.place_given(value, row, col) {
+                        return None;
+                    }
+                }
+            }
+        }
+        let mut candidates: Vec<Vec<Vec<u64>>> = board.grids.iter().map(|grid| solver::candidate_masks(grid, GRID_SIZE as i32)).collect();
+        board.share(&mut candidates);
+        let cells: Vec<(usize, usize)> = (0..n).flat_map(|r| (0..n).map(move |c| (r, c))).filter(|&(r, c)| self.layout.is_used(r, c)).collect();
+        // a guess that can't work out can take a very long time to run into trouble when the grids barely overlap, so
+        // rather than backtracking all the way out of it the search starts over with other guesses, allowing itself
+        // twice as many each time, up to MAX_RESTARTS times
+        let mut guesses = 1000;
+        for _ in 0..=MAX_RESTARTS {
+            board.guesses_left = guesses;
+            if board.search(candidates.clone(), &cells) {
+                return Some((0..n).map(|r| (0..n).map(|c| board.value(r, c)).collect()).collect());
+            }
+            if board.guesses_left > 0 {
+                return None; // every guess was tried
+            }
+            guesses *= 2;
+        }
+        None
+    }
+}
+
+// The boards of every grid of a layout, solved together. Each grid keeps its own candidates, narrowed down the same
+// way solver::solve_board does it, and a square where grids overlap can only be the values left in all of them.
+struct MultiBoard {
+    layout: Layout,
+    grids: Vec<SudokuBoard>,
+    units: Vec<Vec<(usize, usize)>>, // the rows, columns and boxes of a grid, the same for all of them
+    shared: Vec<Vec<(usize, (usize, usize))>>, // the squares in more than one grid, as Layout::grids_at gives them
+    guesses_left: usize, // how many more guesses the search may make before it gives up and starts over
+}
+
+impl MultiBoard {
+    fn value(&self, row: usize, col: usize) -> Option<i32> {
+        self.layout.grids_at(row, col).first().and_then(|&(i, (r, c))| self.grids[i].values[r][c])
+    }
+
+    // Places a given in every grid the square is in, or none of them if it doesn't fit one
+    fn place_given(&mut self, value: i32, row: usize, col: usize) -> bool {
+        let grids = self.layout.grids_at(row, col);
+        for (placed, &(i, (r, c))) in grids.iter().enumerate() {
+            if !solver::update_board(&mut self.grids[i], value, r, c) {
+                for &(j, (r, c)) in &grids[..placed] {
+                    solver::remove_val(&mut self.grids[j], value, r, c);
+                }
+                return false;
+            }
+        }
+        true
+    }
+
+    // Like place_given, but also narrows down the candidates of every grid the square is in
+    fn place(&mut self, candidates: &mut [Vec<Vec<u64>>], value: i32, row: usize, col: usize) -> bool {
+        let grids = self.layout.grids_at(row, col);
+        for (placed, &(i, (r, c))) in grids.iter().enumerate() {
+            if !solver::place(&mut self.grids[i], &mut candidates[i], &self.units, value, r, c) {
+                for &(j, (r, c)) in &grids[..placed] {
+                    solver::remove_val(&mut self.grids[j], value, r, c);
+                }
+                return false;
+            }
+        }
+        self.share(candidates);
+        true
+    }
+
+    // Gives every grid the values left in the squares it shares with other grids, so that each of them finds the
+    // singles the others' rows, columns and boxes make
+    fn share(&self, candidates: &mut [Vec<Vec<u64>>]) {
+        for grids in &self.shared {
+            let mask = grids.iter().fold(!0, |mask, &(i, (r, c))| mask & candidates[i][r][c]);
+            for &(i, (r, c)) in grids {
+                candidates[i][r][c] = mask;
+            }
+        }
+    }
+
+    fn remove(&mut self, value: i32, row: usize, col: usize) {
+        for (i, (r, c)) in self.layout.grids_at(row, col) {
+            solver::remove_val(&mut self.grids[i], value, r, c);
+        }
+    }
+
+    // The values a square could still have in every grid it is in
+    fn candidates(&self, candidates: &[Vec<Vec<u64>>], row: usize, col: usize) -> u64 {
+        self.layout.grids_at(row, col).iter().fold(!0, |mask, &(i, (r, c))| mask & candidates[i][r][c])
+    }
+
+    // Fills in whatever any one grid forces, then guesses at the square with the fewest values left across its
+    // grids. Returns whether every square got filled in, leaving the grids as they were if not.
+    fn search(&mut self, mut candidates: Vec<Vec<Vec<u64>>>, cells: &[(usize, usize)]) -> bool {
+        let offsets = self.layout.offsets();
+        let mut placed: Vec<(i32, usize, usize)> = Vec::new();
+        let solved = 'search: loop {
+            let mut forced = None;
+            for (i, grid) in self.grids.iter().enumerate() {
+                match solver::find_forced(grid, &candidates[i], &self.units) {
+                    Forced::Value(value, r, c) => {
+                        forced = Some((value, offsets[i].0 + r, offsets[i].1 + c));
+                        break;
+                    }
+                    Forced::Stuck => break 'search false,
+                    Forced::Guess => {}
+                }
+            }
+            if let Some((value, row, col)) = forced {
+                if !self.place(&mut candidates, value, row, col) {
+                    break false;
+                }
+                placed.push((value, row, col));
+                continue;
+            }
+            let empty = cells.iter().filter(|&&(r, c)| self.value(r, c).is_none());
+            let (row, col) = match empty.min_by_key(|&&(r, c)| self.candidates(&candidates, r, c).count_ones()) {
+                Some(&cell) => cell,
+                None => break true, // every square is filled in
+            };
+            if self.guesses_left == 0 {
+                break false;
+            }
+            self.guesses_left -= 1;
+            let mask = self.candidates(&candidates, row, col);
+            let mut guesses: Vec<i32> = (1..=GRID_SIZE as i32).filter(|v| mask & 1 << (v - 1) != 0).collect();
+            guesses.shuffle(&mut rand::thread_rng());
+            break guesses.into_iter().any(|value| {
+                let mut after = candidates.clone();
+                if !self.place(&mut after, value, row, col) {
+                    return false;
+                }
+                self.search(after, cells) || {
+                    self.remove(value, row, col);
+                    false
+                }
+            });
+        };
+        if !solved {
+            for &(value, row, col) in placed.iter().rev() {
+                self.remove(value, row, col);
+            }
+        }
+        solved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Whether every row, column and box of every grid has each value once
+    fn grids_are_solved(layout: Layout, values: &[Vec<Option<i32>>]) -> bool {
+        let every_value: Vec<i32> = (1..=GRID_SIZE as i32).collect();
+        layout.offsets().iter().all(|&(top, left)| (0..GRID_SIZE).all(|i| {
+            let unit = |squares: Vec<(usize, usize)>| {
+                let mut unit: Vec<i32> = squares.iter().filter_map(|&(r, c)| values[top + r][left + c]).collect();
+                unit.sort();
+                unit == every_value
+            };
+            unit((0..GRID_SIZE).map(|j| (i, j)).collect())
+                && unit((0..GRID_SIZE).map(|j| (j, i)).collect())
+                && unit((0..GRID_SIZE).map(|j| (i / 3 * 3 + j / 3, i % 3 * 3 + j % 3)).collect())
+        }))
+    }
+
+    #[test]
+    fn solves_every_layout() {
+        for layout in [Layout::Samurai, Layout::Butterfly, Layout::Twin] {
+            let values = MultiPuzzle::new(layout).solve().unwrap();
+            assert!(grids_are_solved(layout, &values));
+            // and again from some of those values, which have to be kept
+            let mut puzzle = MultiPuzzle::new(layout);
+            let n = layout.canvas_size();
+            for square in (0..n * n).step_by(7) {
+                puzzle.givens[square / n][square % n] = values[square / n][square % n];
+            }
+            let solved = puzzle.solve().unwrap();
+            assert!(grids_are_solved(layout, &solved));
+            assert!((0..n).all(|r| (0..n).all(|c| puzzle.givens[r][c].is_none() || puzzle.givens[r][c] == solved[r][c])));
+        }
+    }
+
+    #[test]
+    fn overlapping_rows_share_values() {
+        // the two top grids of a butterfly share six squares of row 1, so the other three of each hold the same
+        // values, and a 4 to the right of the 1, 2 and 3 on the left can't fit
+        let mut puzzle = MultiPuzzle::new(Layout::Butterfly);
+        puzzle.givens[0][0] = Some(1);
+        puzzle.givens[0][1] = Some(2);
+        puzzle.givens[0][2] = Some(3);
+        puzzle.givens[0][9] = Some(4);
+        assert_eq!(puzzle.solve(), None);
+    }
+
+    #[test]
+    fn unsolvable_samurai() {
+        // row 7 of the top left grid leaves 7, 8 and 9 for the three squares it shares with the middle grid, whose
+        // top row already has its 7 further along, so only two values are left for those three squares
+        let mut puzzle = MultiPuzzle::new(Layout::Samurai);
+        for col in 0..6 {
+            puzzle.givens[6][col] = Some(col as i32 + 1);
+        }
+        puzzle.givens[6][12] = Some(7);
+        assert!(puzzle.check().is_ok());
+        assert_eq!(puzzle.solve(), None);
+    }
+
+    #[test]
+    fn check() {
+        let mut puzzle = MultiPuzzle::new(Layout::Samurai);
+        assert!(puzzle.check().is_ok());
+        puzzle.givens[0][10] = Some(1); // between the top grids
+        assert!(puzzle.check().is_err());
+        puzzle.givens[0][10] = None;
+        puzzle.givens[0][0] = Some(10);
+        assert!(puzzle.check().is_err());
+        assert!(MultiPuzzle { layout: Layout::Twin, givens: vec![vec![None; 9]; 9] }.check().is_err());
+    }
+}
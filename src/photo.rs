@@ -0,0 +1,357 @@
+// Reading a puzzle from a photo or screenshot of a printed grid
+//
+// The steps are:
+//  1. decode the PNG or JPEG into grayscale and mark the dark ("ink") pixels with an adaptive threshold, so uneven
+//     lighting across a photo doesn't matter
+//  2. take the biggest connected blob of ink as the grid, and its four outermost points as the grid's corners
+//  3. undo the perspective by mapping those corners onto a square
+//  4. work out the number of rows from where the grid lines are, trying each supported size
+//  5. cut out the digit in every square and compare it with digits drawn from the fonts built into the program
+//
+// The result is only a best guess, so the GUI loads it as entries for the player to check before setting the givens.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+
+use crate::formats;
+use crate::puzzle::Puzzle;
+use crate::raster::Canvas;
+
+const WARPED_SIZE: usize = 720; // side of the straightened grid in pixels, the lcm of SIDE_LENGTHS so every square is whole
+const GLYPH_SIZE: usize = 16; // digits are compared as GLYPH_SIZE x GLYPH_SIZE grids of ink
+const MAX_INPUT_SIZE: u32 = 1200; // bigger photos are scaled down first, they don't need the detail
+const SIDE_LENGTHS: [i32; 7] = [4, 6, 8, 9, 10, 12, 16];
+const MIN_MATCH: f64 = 0.4; // squares that look less like a digit than this are left empty
+
+struct Gray {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Gray {
+    fn get(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * self.width + x]
+    }
+
+    // Samples between pixels by blending the four around (x, y), treating anything outside the image as white
+    fn sample(&self, x: f64, y: f64) -> u8 {
+        if x < 0.0 || y < 0.0 || x >= (self.width - 1) as f64 || y >= (self.height - 1) as f64 {
+            return 255;
+        }
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+        let top = self.get(x0, y0) as f64 * (1.0 - fx) + self.get(x0 + 1, y0) as f64 * fx;
+        let bottom = self.get(x0, y0 + 1) as f64 * (1.0 - fx) + self.get(x0 + 1, y0 + 1) as f64 * fx;
+        (top * (1.0 - fy) + bottom * fy).round() as u8
+    }
+
+    // Marks pixels noticeably darker than the average of the area around them
+    fn ink(&self) -> Vec<bool> {
+        let (w, h) = (self.width, self.height);
+        let radius = (w.max(h) / 40).max(4);
+        let mut integral = vec![0u64; (w + 1) * (h + 1)];
+        for y in 0..h {
+            let mut row_sum = 0u64;
+            for x in 0..w {
+                row_sum += self.get(x, y) as u64;
+                integral[(y + 1) * (w + 1) + x + 1] = integral[y * (w + 1) + x + 1] + row_sum;
+            }
+        }
+        let mut result = vec![false; w * h];
+        for y in 0..h {
+            for x in 0..w {
+                let (x0, y0) = (x.saturating_sub(radius), y.saturating_sub(radius));
+                let (x1, y1) = ((x + radius + 1).min(w), (y + radius + 1).min(h));
+                let sum = integral[y1 * (w + 1) + x1] + integral[y0 * (w + 1) + x0] - integral[y0 * (w + 1) + x1] - integral[y1 * (w + 1) + x0];
+                let mean = sum as f64 / ((x1 - x0) * (y1 - y0)) as f64;
+                result[y * w + x] = (self.get(x, y) as f64) < mean * 0.85;
+            }
+        }
+        result
+    }
+}
+
+// Finds every group of touching ink pixels within the rectangle [x0, x1) x [y0, y1), as lists of (x, y)
+fn components(ink: &[bool], width: usize, (x0, y0, x1, y1): (usize, usize, usize, usize)) -> Vec<Vec<(usize, usize)>> {
+    let mut seen = vec![false; ink.len()];
+    let mut result = Vec::new();
+    for y in y0..y1 {
+        for x in x0..x1 {
+            if !ink[y * width + x] || seen[y * width + x] {
+                continue;
+            }
+            let mut component = Vec::new();
+            let mut queue = VecDeque::from([(x, y)]);
+            seen[y * width + x] = true;
+            while let Some((cx, cy)) = queue.pop_front() {
+                component.push((cx, cy));
+                for (dx, dy) in [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)] {
+                    let (nx, ny) = (cx as i64 + dx, cy as i64 + dy);
+                    if nx < x0 as i64 || ny < y0 as i64 || nx >= x1 as i64 || ny >= y1 as i64 {
+                        continue;
+                    }
+                    let i = ny as usize * width + nx as usize;
+                    if ink[i] && !seen[i] {
+                        seen[i] = true;
+                        queue.push_back((nx as usize, ny as usize));
+                    }
+                }
+            }
+            result.push(component);
+        }
+    }
+    result
+}
+
+fn bounding_box(points: &[(usize, usize)]) -> (usize, usize, usize, usize) {
+    let min_x = points.iter().map(|p| p.0).min().unwrap();
+    let min_y = points.iter().map(|p| p.1).min().unwrap();
+    let max_x = points.iter().map(|p| p.0).max().unwrap();
+    let max_y = points.iter().map(|p| p.1).max().unwrap();
+    (min_x, min_y, max_x + 1, max_y + 1)
+}
+
+// Returns the top left, top right, bottom right and bottom left corners of the grid
+fn find_corners(gray: &Gray) -> Result<[(f64, f64); 4], String> {
+    let ink = gray.ink();
+    let grid = components(&ink, gray.width, (0, 0, gray.width, gray.height))
+        .into_iter()
+        .max_by_key(|c| {
+            let (x0, y0, x1, y1) = bounding_box(c);
+            (x1 - x0) * (y1 - y0)
+        })
+        .ok_or("The image is blank")?;
+    let (x0, y0, x1, y1) = bounding_box(&grid);
+    if (x1 - x0) * (y1 - y0) < gray.width * gray.height / 10 {
+        return Err("Could not find a grid in the image".to_string());
+    }
+
+    let corner = |score: &dyn Fn(f64, f64) -> f64| {
+        let best = grid.iter().max_by(|a, b| score(a.0 as f64, a.1 as f64).total_cmp(&score(b.0 as f64, b.1 as f64))).unwrap();
+        (best.0 as f64 + 0.5, best.1 as f64 + 0.5)
+    };
+    Ok([corner(&|x, y| -x - y), corner(&|x, y| x - y), corner(&|x, y| x + y), corner(&|x, y| y - x)])
+}
+
+// Solves for the perspective transform taking the corners of a size x size square onto `corners`
+fn homography(corners: &[(f64, f64); 4], size: f64) -> Result<[f64; 8], String> {
+    let square = [(0.0, 0.0), (size, 0.0), (size, size), (0.0, size)];
+    let mut rows: Vec<[f64; 9]> = Vec::new();
+    for ((u, v), (x, y)) in square.iter().zip(corners.iter()) {
+        rows.push([*u, *v, 1.0, 0.0, 0.0, 0.0, -u * x, -v * x, *x]);
+        rows.push([0.0, 0.0, 0.0, *u, *v, 1.0, -u * y, -v * y, *y]);
+    }
+    // gaussian elimination with partial pivoting
+    for col in 0..8 {
+        let pivot = (col..8).max_by(|a, b| rows[*a][col].abs().total_cmp(&rows[*b][col].abs())).unwrap();
+        rows.swap(col, pivot);
+        if rows[col][col].abs() < 1e-9 {
+            return Err("The corners of the grid are not a proper four sided shape".to_string());
+        }
+        for row in 0..8 {
+            if row != col {
+                let factor = rows[row][col] / rows[col][col];
+                let pivot_row = rows[col];
+                for (k, value) in rows[row].iter_mut().enumerate().skip(col) {
+                    *value -= factor * pivot_row[k];
+                }
+            }
+        }
+    }
+    let mut h = [0.0; 8];
+    for (i, value) in h.iter_mut().enumerate() {
+        *value = rows[i][8] / rows[i][i];
+    }
+    Ok(h)
+}
+
+fn warp(gray: &Gray, corners: &[(f64, f64); 4]) -> Result<Gray, String> {
+    let h = homography(corners, WARPED_SIZE as f64)?;
+    let mut pixels = Vec::with_capacity(WARPED_SIZE * WARPED_SIZE);
+    for v in 0..WARPED_SIZE {
+        for u in 0..WARPED_SIZE {
+            let (u, v) = (u as f64 + 0.5, v as f64 + 0.5);
+            let w = h[6] * u + h[7] * v + 1.0;
+            pixels.push(gray.sample((h[0] * u + h[1] * v + h[2]) / w, (h[3] * u + h[4] * v + h[5]) / w));
+        }
+    }
+    Ok(Gray { width: WARPED_SIZE, height: WARPED_SIZE, pixels })
+}
+
+// Picks the side length whose grid lines line up best with the lines in the straightened image
+fn detect_side_length(ink: &[bool]) -> i32 {
+    let mut row_profile = vec![0.0; WARPED_SIZE];
+    let mut col_profile = vec![0.0; WARPED_SIZE];
+    for y in 0..WARPED_SIZE {
+        for x in 0..WARPED_SIZE {
+            if ink[y * WARPED_SIZE + x] {
+                row_profile[y] += 1.0 / WARPED_SIZE as f64;
+                col_profile[x] += 1.0 / WARPED_SIZE as f64;
+            }
+        }
+    }
+    // the most ink in any row (or column) within a few pixels, as lines are rarely exactly where they should be
+    let near = |profile: &[f64], at: f64| {
+        let from = (at as i64 - 3).max(0) as usize;
+        let to = (at as usize + 4).min(WARPED_SIZE);
+        profile[from..to].iter().cloned().fold(0.0, f64::max)
+    };
+    let score = |n: i32| {
+        let cell = WARPED_SIZE as f64 / n as f64;
+        let mut total = 0.0;
+        for profile in [&row_profile, &col_profile] {
+            // lines should be where the grid lines go, and there should be no lines halfway between them
+            total += (1..n).map(|k| near(profile, k as f64 * cell)).sum::<f64>() / (n - 1) as f64;
+            total -= (0..n).map(|k| near(profile, (k as f64 + 0.5) * cell)).sum::<f64>() / n as f64;
+        }
+        total
+    };
+    let best = *SIDE_LENGTHS.iter().max_by(|a, b| score(**a).total_cmp(&score(**b))).unwrap();
+    // a grid has every line that a grid of fewer, bigger squares would (a 12x12 grid with 3x4 boxes has all the lines of
+    // a 4x4 one), so a multiple of the best size that lines up nearly as well is the real size
+    *SIDE_LENGTHS.iter().filter(|n| **n % best == 0 && score(**n) > 0.6 * score(best)).max().unwrap()
+}
+
+// Shrinks a patch of ink down to GLYPH_SIZE x GLYPH_SIZE, keeping its shape and centering it
+fn normalize(ink: &[bool], width: usize, (x0, y0, x1, y1): (usize, usize, usize, usize)) -> Vec<f64> {
+    let (w, h) = ((x1 - x0) as f64, (y1 - y0) as f64);
+    let scale = w.max(h) / GLYPH_SIZE as f64;
+    let (offset_x, offset_y) = ((w.max(h) - w) / 2.0, (w.max(h) - h) / 2.0);
+    let mut result = vec![0.0; GLYPH_SIZE * GLYPH_SIZE];
+    for gy in 0..GLYPH_SIZE {
+        for gx in 0..GLYPH_SIZE {
+            // average the ink over the part of the patch that lands on this spot
+            let (sx0, sy0) = (gx as f64 * scale - offset_x, gy as f64 * scale - offset_y);
+            let (mut inked, mut total) = (0.0, 0.0);
+            let steps = 4;
+            for sy in 0..steps {
+                for sx in 0..steps {
+                    let x = sx0 + (sx as f64 + 0.5) * scale / steps as f64;
+                    let y = sy0 + (sy as f64 + 0.5) * scale / steps as f64;
+                    total += 1.0;
+                    if x >= 0.0 && y >= 0.0 && x < w && y < h && ink[(y0 + y as usize) * width + x0 + x as usize] {
+                        inked += 1.0;
+                    }
+                }
+            }
+            result[gy * GLYPH_SIZE + gx] = inked / total;
+        }
+    }
+    // centered and scaled to length 1 so that comparing two glyphs is a dot product
+    let mean = result.iter().sum::<f64>() / result.len() as f64;
+    result.iter_mut().for_each(|v| *v -= mean);
+    let length = result.iter().map(|v| v * v).sum::<f64>().sqrt().max(1e-9);
+    result.iter().map(|v| v / length).collect()
+}
+
+// Draws every symbol of a puzzle of the given size in the built in fonts, to compare the squares against
+fn templates(side_length: i32) -> Vec<(i32, Vec<f64>)> {
+    let size = 96;
+    let mut result = Vec::new();
+    for value in 1..=side_length {
+        for bold in [false, true] {
+            let mut canvas = Canvas::new(size as u32, size as u32);
+            canvas.draw_text((size as f64 / 2.0, size as f64 / 2.0), 64.0, bold, (0, 0, 0), &formats::value_symbol(value).to_string());
+            let ink: Vec<bool> = canvas.pixels.chunks(3).map(|p| p[0] < 128).collect();
+            let points: Vec<(usize, usize)> = (0..size * size).filter(|i| ink[*i]).map(|i| (i % size, i / size)).collect();
+            result.push((value, normalize(&ink, size, bounding_box(&points))));
+        }
+    }
+    result
+}
+
+// Reads the digit in every square of the straightened grid
+fn read_cells(ink: &[bool], side_length: i32) -> Puzzle {
+    let mut puzzle = Puzzle::new(side_length);
+    let templates = templates(side_length);
+    let cell = WARPED_SIZE / side_length as usize;
+    let margin = cell / 8; // skip what is left of the grid lines at the edges of the square
+    for r in 0..side_length as usize {
+        for c in 0..side_length as usize {
+            let area = (c * cell + margin, r * cell + margin, (c + 1) * cell - margin, (r + 1) * cell - margin);
+            let digit = components(ink, WARPED_SIZE, area)
+                .into_iter()
+                .filter(|points| {
+                    let (x0, y0, x1, y1) = bounding_box(points);
+                    let touches_edge = x0 == area.0 || y0 == area.1 || x1 == area.2 || y1 == area.3;
+                    !touches_edge && (y1 - y0) * 10 >= cell * 3
+                })
+                .max_by_key(|points| points.len());
+            if let Some(points) = digit {
+                let glyph = normalize(ink, WARPED_SIZE, bounding_box(&points));
+                let (value, similarity) = templates.iter()
+                    .map(|(value, template)| (*value, template.iter().zip(glyph.iter()).map(|(a, b)| a * b).sum::<f64>()))
+                    .max_by(|a, b| a.1.total_cmp(&b.1))
+                    .unwrap();
+                if similarity >= MIN_MATCH {
+                    puzzle.givens[r][c] = Some(value);
+                }
+            }
+        }
+    }
+    puzzle
+}
+
+// Reads a puzzle from the contents of a PNG or JPEG file
+pub fn import_image(bytes: &[u8]) -> Result<Puzzle, String> {
+    let mut image = image::load_from_memory(bytes).map_err(|e| format!("Could not read the image: {}", e))?;
+    if image.width().max(image.height()) > MAX_INPUT_SIZE {
+        image = image.resize(MAX_INPUT_SIZE, MAX_INPUT_SIZE, image::imageops::FilterType::Triangle);
+    }
+    let luma = image.to_luma8();
+    let gray = Gray { width: luma.width() as usize, height: luma.height() as usize, pixels: luma.into_raw() };
+
+    let corners = find_corners(&gray)?;
+    let warped = warp(&gray, &corners)?;
+    let ink = warped.ink();
+    let side_length = detect_side_length(&ink);
+    Ok(read_cells(&ink, side_length))
+}
+
+pub fn import_file(path: &Path) -> Result<Puzzle, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+    import_image(&bytes)
+}
+
+// Whether a file looks like something import_file can read, judging by its extension
+pub fn is_image(path: &Path) -> bool {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    ext == "png" || ext == "jpg" || ext == "jpeg"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raster::{self, RenderOptions};
+
+    #[test]
+    fn reads_a_rendered_puzzle() {
+        let puzzle = formats::parse_sdm(
+            "016400000200009000400000062070230100100000003003087040960000005000800007000006820"
+        ).unwrap().remove(0);
+        let board = raster::render(&puzzle, &RenderOptions { size: 900, ..RenderOptions::default() });
+
+        // put the grid off center on a bigger, grey page, which also has to be scaled down before reading
+        let mut page = Canvas::new(1600, 1400);
+        page.pixels.fill(230);
+        for y in 0..board.height as usize {
+            let from = (y * board.width as usize) * 3;
+            let to = ((y + 300) * page.width as usize + 500) * 3;
+            page.pixels[to..to + board.width as usize * 3].copy_from_slice(&board.pixels[from..from + board.width as usize * 3]);
+        }
+        let imported = import_image(&page.to_png().unwrap()).unwrap();
+        assert_eq!(imported.side_length, 9);
+        assert_eq!(imported.givens, puzzle.givens);
+    }
+
+    #[test]
+    fn needs_a_grid() {
+        let mut page = Canvas::new(400, 300);
+        assert_eq!(import_image(&page.to_png().unwrap()).unwrap_err(), "The image is blank");
+        page.draw_line((20.0, 20.0), (60.0, 20.0), 3.0, (0, 0, 0));
+        assert_eq!(import_image(&page.to_png().unwrap()).unwrap_err(), "Could not find a grid in the image");
+        assert!(import_image(b"size 9\ngivens 1").unwrap_err().starts_with("Could not read the image"));
+    }
+}
@@ -0,0 +1,411 @@
+// The puzzle document: everything needed to store a puzzle and pick it back up later
+//
+// With the `serde` feature enabled, the document, the SudokuBoard and solve results can all be (de)serialized,
+// and formats::Format::Json reads and writes puzzles as JSON.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::rules::{self, AllDifferent, Arrow, Cage, LittleKiller, NoPair, NoRepeatsApart, NonConsecutive, Palindrome, Pair, Parity, Renban, Rule, Sandwich, Thermometer, Whisper};
+use crate::solver::{self, SudokuBoard};
+use crate::symbols::SymbolSet;
+
+// Information about where a puzzle came from, all of it optional
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Metadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub source: Option<String>,
+    pub difficulty: Option<String>,
+    pub comment: Option<String>,
+    pub rules: Option<String>, // how a variant is played, in the setter's own words (may be several lines)
+}
+
+// Markers on the edge between two squares next to each other, saying how their values are related
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum EdgeMarker {
+    WhiteDot, // consecutive values
+    BlackDot, // one value is double the other
+    X, // the values add up to 10
+    V, // the values add up to 5
+    Less, // greater-than sudoku: the first square's value is less than the second's
+}
+
+impl EdgeMarker {
+    // Whether the values on either side of the marker fit it, `a` being the first square's value
+    pub fn relation(&self) -> fn(i32, i32) -> bool {
+        match self {
+            EdgeMarker::WhiteDot => |a, b| (a - b).abs() == 1,
+            EdgeMarker::BlackDot => |a, b| a == 2 * b || b == 2 * a,
+            EdgeMarker::X => |a, b| a + b == 10,
+            EdgeMarker::V => |a, b| a + b == 5,
+            EdgeMarker::Less => |a, b| a < b,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EdgeMarker::WhiteDot => "white",
+            EdgeMarker::BlackDot => "black",
+            EdgeMarker::X => "x",
+            EdgeMarker::V => "v",
+            EdgeMarker::Less => "less",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<EdgeMarker> {
+        [EdgeMarker::WhiteDot, EdgeMarker::BlackDot, EdgeMarker::X, EdgeMarker::V, EdgeMarker::Less].into_iter().find(|marker| marker.name() == name)
+    }
+}
+
+// Lines drawn through the middle of their squares, saying how the values along them are related
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum LineKind {
+    Whisper, // German whispers: squares next to each other along the line differ by at least 5 (on a 9x9 board)
+    Renban, // different values that make a run of consecutive numbers, in any order
+    Palindrome, // the line reads the same from either end
+}
+
+impl LineKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LineKind::Whisper => "whisper",
+            LineKind::Renban => "renban",
+            LineKind::Palindrome => "palindrome",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<LineKind> {
+        [LineKind::Whisper, LineKind::Renban, LineKind::Palindrome].into_iter().find(|kind| kind.name() == name)
+    }
+}
+
+// Rules a puzzle adds on top of the usual row, column and box rules
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Constraint {
+    Diagonals, // Sudoku X: both main diagonals have every value once
+    Windows, // Windoku: extra box shaped regions, see rules::window_units
+    AntiKnight, // squares a chess knight's move apart can't have the same value
+    AntiKing, // squares a chess king's move apart (touching, even diagonally) can't have the same value
+    NonConsecutive, // squares next to each other (not diagonally) can't have consecutive values
+    Cage { cells: Vec<(usize, usize)>, sum: i32 }, // killer sudoku: (row, col) squares adding up to sum, without repeats
+    Thermometer { cells: Vec<(usize, usize)> }, // values go up from the bulb (the first square) along the rest
+    Arrow { circle: Vec<(usize, usize)>, cells: Vec<(usize, usize)> }, // the shaft's cells add up to the circle (or pill)
+    Edge { marker: EdgeMarker, cells: [(usize, usize); 2] }, // a marker between two squares next to each other
+    Line { kind: LineKind, cells: Vec<(usize, usize)> }, // a whisper, renban or palindrome line, in the order drawn
+    Parity { cell: (usize, usize), even: bool }, // a square shaded to say its value is even (a square) or odd (a circle)
+    AllDotsGiven, // Kropki: squares next to each other without a dot can't be consecutive or one double the other
+    AllXVGiven, // XV: squares next to each other without an X or V can't add up to 10 or 5
+    // The clues outside the grid. Their cells go away from the clue, which is written just before the first of them.
+    Sandwich { cells: Vec<(usize, usize)>, sum: i32 }, // a row or column, whose values between the 1 and side_length add up to sum
+    LittleKiller { cells: Vec<(usize, usize)>, sum: i32 }, // a diagonal adding up to sum, values may repeat
+}
+
+impl Constraint {
+    // The rule the solver checks for this constraint on a board with boxes of box_shape. Negative constraints depend on
+    // where the markers are, so they get all of the puzzle's `constraints` too.
+    pub fn rule(&self, side_length: i32, box_shape: (i32, i32), constraints: &[Constraint]) -> Box<dyn Rule> {
+        // the pairs of squares with one of the `markers` between them, top left square first
+        let marked = |markers: &[EdgeMarker]| constraints.iter().filter_map(|constraint| match constraint {
+            Constraint::Edge { marker, cells: [a, b] } if markers.contains(marker) => Some(if a < b {[*a, *b]} else {[*b, *a]}),
+            _ => None,
+        }).collect();
+        match self {
+            Constraint::Diagonals => Box::new(AllDifferent { units: rules::diagonal_units(side_length) }),
+            Constraint::Windows => Box::new(AllDifferent { units: rules::window_units(side_length, box_shape) }),
+            Constraint::AntiKnight => Box::new(NoRepeatsApart { moves: rules::KNIGHT_MOVES.to_vec() }),
+            Constraint::AntiKing => Box::new(NoRepeatsApart { moves: rules::KING_MOVES.to_vec() }),
+            Constraint::NonConsecutive => Box::new(NonConsecutive),
+            Constraint::Cage { cells, sum } => Box::new(Cage::new(cells.clone(), *sum, side_length)),
+            Constraint::Thermometer { cells } => Box::new(Thermometer { cells: cells.clone() }),
+            Constraint::Arrow { circle, cells } => Box::new(Arrow { circle: circle.clone(), cells: cells.clone() }),
+            Constraint::Edge { marker, cells } => Box::new(Pair { cells: *cells, holds: marker.relation() }),
+            Constraint::AllDotsGiven => Box::new(NoPair { marked: marked(&[EdgeMarker::WhiteDot, EdgeMarker::BlackDot]), relations: vec![EdgeMarker::WhiteDot.relation(), EdgeMarker::BlackDot.relation()] }),
+            Constraint::AllXVGiven => Box::new(NoPair { marked: marked(&[EdgeMarker::X, EdgeMarker::V]), relations: vec![EdgeMarker::X.relation(), EdgeMarker::V.relation()] }),
+            Constraint::Sandwich { cells, sum } => Box::new(Sandwich { cells: cells.clone(), sum: *sum }),
+            // whispers are half the values apart, rounded up, which is 5 on a 9x9 board
+            Constraint::Line { kind: LineKind::Whisper, cells } => Box::new(Whisper { cells: cells.clone(), gap: (side_length + 1) / 2 }),
+            Constraint::Line { kind: LineKind::Renban, cells } => Box::new(Renban { cells: cells.clone() }),
+            Constraint::Line { kind: LineKind::Palindrome, cells } => Box::new(Palindrome { cells: cells.clone() }),
+            Constraint::Parity { cell, even } => Box::new(Parity { cell: *cell, even: *even }),
+            Constraint::LittleKiller { cells, sum } => Box::new(LittleKiller { cells: cells.clone(), sum: *sum }),
+        }
+    }
+
+    // The squares the constraint is drawn on and applies to
+    pub fn cells(&self) -> Vec<(usize, usize)> {
+        match self {
+            Constraint::Diagonals | Constraint::Windows | Constraint::AntiKnight | Constraint::AntiKing | Constraint::NonConsecutive
+                | Constraint::AllDotsGiven | Constraint::AllXVGiven => Vec::new(),
+            Constraint::Cage { cells, .. } | Constraint::Thermometer { cells } | Constraint::Line { cells, .. } | Constraint::Sandwich { cells, .. }
+                | Constraint::LittleKiller { cells, .. } => cells.clone(),
+            Constraint::Arrow { circle, cells } => circle.iter().chain(cells).copied().collect(),
+            Constraint::Edge { cells, .. } => cells.to_vec(),
+            Constraint::Parity { cell, .. } => vec![*cell],
+        }
+    }
+
+    // Where a clue outside the grid is written and which way its line goes, for sandwiches and little killers
+    pub fn outside_clue(&self) -> Option<OutsideClue> {
+        match self {
+            Constraint::Sandwich { cells, sum } | Constraint::LittleKiller { cells, sum } if cells.len() >= 2 => {
+                let at = |i: usize| (cells[i].0 as i64, cells[i].1 as i64);
+                let step = (at(1).0 - at(0).0, at(1).1 - at(0).1);
+                Some(OutsideClue { at: (at(0).0 - step.0, at(0).1 - step.1), step, sum: *sum })
+            }
+            _ => None,
+        }
+    }
+}
+
+// A clue outside the grid, as laid out around the board
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OutsideClue {
+    pub at: (i64, i64), // (row, col) counting from the top left square, so -1 is above or left of the grid
+    pub step: (i64, i64), // the (row, col) step its line takes into the grid
+    pub sum: i32,
+}
+
+// The squares of a board of side_length met going from `clue`, outside the grid, in steps of `step` until leaving it
+pub fn outside_line(side_length: i32, clue: (i64, i64), step: (i64, i64)) -> Vec<(usize, usize)> {
+    let n = side_length as i64;
+    let mut cells = Vec::new();
+    let (mut r, mut c) = (clue.0 + step.0, clue.1 + step.1);
+    while step != (0, 0) && r >= 0 && r < n && c >= 0 && c < n {
+        cells.push((r as usize, c as usize));
+        r += step.0;
+        c += step.1;
+    }
+    cells
+}
+
+// The rules the solver checks for all of a puzzle's constraints
+pub fn board_rules(constraints: &[Constraint], side_length: i32, box_shape: (i32, i32)) -> Vec<Box<dyn Rule>> {
+    constraints.iter().map(|constraint| constraint.rule(side_length, box_shape, constraints)).collect()
+}
+
+// A puzzle as read from (or written to) a file, keeping apart what the puzzle gave and what the player entered
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Puzzle {
+    pub side_length: i32,
+    pub box_shape: (i32, i32), // rows x cols of each bold box
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub regions: Option<Vec<Vec<usize>>>, // irregular regions used instead of the boxes (for jigsaw puzzles), numbered from 0
+    pub givens: Vec<Vec<Option<i32>>>,
+    pub entries: Vec<Vec<Option<i32>>>, // values placed by the player, never set where there is a given
+    pub candidates: Vec<Vec<Vec<i32>>>, // pencil marks, an empty list means the cell has none
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub elapsed_secs: u64, // time spent solving so far
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub symbols: SymbolSet, // how values are shown, never one with fewer than side_length symbols
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub constraints: Vec<Constraint>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub metadata: Metadata,
+}
+
+// The outcome of solving a puzzle: whether it worked, and the filled in values if it did
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SolveResult {
+    pub solved: bool,
+    pub values: Vec<Vec<Option<i32>>>,
+}
+
+impl Puzzle {
+    // An empty puzzle with the usual box shape for its size (or a single row per box, if it can't be split into boxes)
+    pub fn new(side_length: i32) -> Puzzle {
+        Puzzle {
+            side_length,
+            box_shape: solver::default_box_shape(side_length).unwrap_or((1, side_length)),
+            regions: None,
+            givens: vec![vec![None; side_length as usize]; side_length as usize],
+            entries: vec![vec![None; side_length as usize]; side_length as usize],
+            candidates: vec![vec![Vec::new(); side_length as usize]; side_length as usize],
+            elapsed_secs: 0,
+            symbols: SymbolSet::default_for(side_length),
+            constraints: Vec::new(),
+            metadata: Metadata::default(),
+        }
+    }
+
+    // Returns the value showing in a cell, whether it was given or entered
+    pub fn value(&self, row: usize, col: usize) -> Option<i32> {
+        self.givens[row][col].or(self.entries[row][col])
+    }
+
+    // The region every square is in: the jigsaw regions if there are any, otherwise the boxes
+    pub fn region_map(&self) -> Vec<Vec<usize>> {
+        match &self.regions {
+            Some(regions) => regions.clone(),
+            None => solver::box_regions(self.side_length, self.box_shape),
+        }
+    }
+
+    pub fn check_regions(&self) -> Result<(), String> {
+        if solver::regions_are_valid(&self.region_map(), self.side_length) {
+            Ok(())
+        } else {
+            Err(format!("Every region must have exactly {} squares", self.side_length))
+        }
+    }
+
+    // Checks everything a hand-edited or generated file could get wrong, so that a puzzle passing it can be solved and
+    // shown without indexing outside the board
+    pub fn check(&self) -> Result<(), String> {
+        let n = self.side_length;
+        if n < 2 || !SymbolSet::Alphanumeric.supports(n) || !self.symbols.supports(n) {
+            return Err(format!("A side length of {} is not a supported puzzle size", n));
+        }
+        let (box_rows, box_cols) = self.box_shape;
+        if box_rows < 1 || box_cols < 1 || box_rows * box_cols != n {
+            return Err(format!("{}x{} is not a valid box shape for a {}x{} puzzle", box_rows, box_cols, n, n));
+        }
+        let is_square = |lengths: Vec<usize>| lengths.len() == n as usize && lengths.iter().all(|len| *len == n as usize);
+        if !is_square(self.givens.iter().map(Vec::len).collect()) || !is_square(self.entries.iter().map(Vec::len).collect())
+            || !is_square(self.candidates.iter().map(Vec::len).collect()) {
+            return Err("The givens, entries and candidates must be the same size as the puzzle".to_string());
+        }
+        let values = self.givens.iter().chain(&self.entries).flatten().flatten();
+        if values.chain(self.candidates.iter().flatten().flatten()).any(|v| *v < 1 || *v > n) {
+            return Err(format!("Values must be between 1 and {}", n));
+        }
+        self.check_regions()?;
+        self.check_constraints()
+    }
+
+    // Checks that every constraint only uses squares on the board
+    pub fn check_constraints(&self) -> Result<(), String> {
+        let n = self.side_length as usize;
+        let regions = self.region_map();
+        let region = |(r, c): (usize, usize)| regions.get(r).and_then(|row| row.get(c));
+        for constraint in &self.constraints {
+            match constraint {
+                Constraint::Cage { cells, .. } if cells.is_empty() => return Err("A cage must have at least one square".to_string()),
+                Constraint::Cage { cells, .. } if cells.len() > n => return Err(format!("A cage can't have more than {} squares without repeating a value", n)),
+                Constraint::Thermometer { cells } if cells.len() < 2 => return Err("A thermometer must have at least two squares".to_string()),
+                Constraint::Line { cells, .. } if cells.len() < 2 => return Err("A line must have at least two squares".to_string()),
+                Constraint::Arrow { circle, cells } if circle.is_empty() || cells.is_empty() => return Err("An arrow must have a circle and a shaft".to_string()),
+                Constraint::Arrow { circle, .. } if circle.len() > 1 && n > 9 => {
+                    return Err("A pill is read one digit per square, so it can only be used on boards of up to 9x9".to_string());
+                }
+                Constraint::Edge { cells: [(r1, c1), (r2, c2)], .. } if r1.abs_diff(*r2) + c1.abs_diff(*c2) != 1 => return Err("Markers must be between squares next to each other".to_string()),
+                // greater-than sudoku only puts its signs between squares of the same box (or jigsaw region)
+                Constraint::Edge { marker: EdgeMarker::Less, cells: [a, b] } if region(*a) != region(*b) => {
+                    return Err("Inequality signs must be between squares in the same box".to_string());
+                }
+                _ => {}
+            }
+            // an outside clue's line has to run all the way across the board from the clue, straight for sandwiches and
+            // diagonally for little killers
+            if let Constraint::Sandwich { cells, .. } | Constraint::LittleKiller { cells, .. } = constraint {
+                let diagonal = matches!(constraint, Constraint::LittleKiller { .. });
+                let fits = match constraint.outside_clue() {
+                    Some(OutsideClue { at, step, .. }) => step.0.abs() <= 1 && step.1.abs() <= 1 && (step.0 != 0 && step.1 != 0) == diagonal
+                        && outside_line(self.side_length, at, step) == *cells,
+                    None => false,
+                };
+                if !fits && diagonal {
+                    return Err("A little killer must be a whole diagonal of two or more squares, going away from its clue".to_string());
+                } else if !fits {
+                    return Err("A sandwich must be a whole row or column, going away from its clue".to_string());
+                }
+            }
+            if constraint.cells().iter().any(|&(r, c)| r >= n || c >= n) {
+                return Err(format!("Constraints must be inside the {}x{} board", n, n));
+            }
+        }
+        Ok(())
+    }
+
+    // Places every given and entry into a fresh SudokuBoard, returning None if any of them conflict (or the regions or constraints are invalid)
+    pub fn to_board(&self) -> Option<SudokuBoard> {
+        self.check_regions().ok()?;
+        self.check_constraints().ok()?;
+        let mut board = solver::new_board(self.side_length, self.region_map());
+        board.rules = board_rules(&self.constraints, self.side_length, self.box_shape);
+        for r in 0..self.side_length as usize {
+            for c in 0..self.side_length as usize {
+                if let Some(v) = self.value(r, c) {
+                    if !solver::update_board(&mut board, v, r, c) {
+                        return None;
+                    }
+                }
+            }
+        }
+        Some(board)
+    }
+
+    // Candidates that the rules alone allow for every empty cell, or None if the givens and entries already break the rules
+    pub fn rule_candidates(&self) -> Option<Vec<Vec<Vec<i32>>>> {
+        let board = self.to_board()?;
+        let side_length = self.side_length;
+        Some((0..side_length as usize).map(|r| (0..side_length as usize).map(|c| {
+            if board.values[r][c].is_some() {
+                Vec::new()
+            } else {
+                solver::possible_vals(&board, r, c, solver::get_box_num(&board, r, c), side_length)
+            }
+        }).collect()).collect())
+    }
+
+    // Solves the puzzle starting from the givens only, ignoring whatever the player has entered
+    pub fn solve(&self) -> SolveResult {
+        let givens_only = Puzzle { entries: vec![vec![None; self.side_length as usize]; self.side_length as usize], ..self.clone() };
+        if let Some(mut board) = givens_only.to_board() {
+            if solver::solve_board(&mut board, self.side_length) {
+                return SolveResult { solved: true, values: board.values };
+            }
+        }
+        SolveResult { solved: false, values: self.givens.clone() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diagonals_have_every_value() {
+        let mut puzzle = Puzzle::new(9);
+        puzzle.constraints.push(Constraint::Diagonals);
+        let result = puzzle.solve();
+        assert!(result.solved);
+        for diagonal in rules::diagonal_units(9) {
+            let mut values: Vec<i32> = diagonal.iter().filter_map(|&(r, c)| result.values[r][c]).collect();
+            values.sort();
+            assert_eq!(values, (1..=9).collect::<Vec<i32>>());
+        }
+    }
+
+    #[test]
+    fn inequalities_stay_inside_boxes() {
+        let mut puzzle = Puzzle::new(9);
+        puzzle.constraints.push(Constraint::Edge { marker: EdgeMarker::Less, cells: [(0, 1), (0, 2)] });
+        assert!(puzzle.check_constraints().is_ok());
+        puzzle.constraints.push(Constraint::Edge { marker: EdgeMarker::Less, cells: [(0, 2), (0, 3)] });
+        assert!(puzzle.check_constraints().is_err());
+
+        // other markers may cross a box border
+        puzzle.constraints.pop();
+        puzzle.constraints.push(Constraint::Edge { marker: EdgeMarker::WhiteDot, cells: [(0, 2), (0, 3)] });
+        assert!(puzzle.check_constraints().is_ok());
+    }
+
+    #[test]
+    fn pills_need_single_digits() {
+        let pill = Constraint::Arrow { circle: vec![(0, 0), (0, 1)], cells: vec![(1, 0), (2, 0), (3, 0)] };
+        let mut puzzle = Puzzle::new(9);
+        puzzle.constraints.push(pill.clone());
+        assert!(puzzle.check_constraints().is_ok());
+        let mut puzzle = Puzzle::new(12);
+        puzzle.constraints.push(pill);
+        assert!(puzzle.check_constraints().is_err());
+    }
+}
@@ -0,0 +1,202 @@
+// Offscreen rendering of puzzles to PNG images, without needing a window or a graphics card
+//
+// The board is laid out with export::draw_board, the same as for SVG and PDF, and its shapes are then drawn onto
+// an RGB pixel buffer using the Noto Sans fonts that are built into the program.
+
+use std::fs;
+use std::path::Path;
+
+use rusttype::{point, Font, Scale};
+
+use crate::export::{self, Shape};
+use crate::puzzle::Puzzle;
+
+const REGULAR_FONT: &[u8] = include_bytes!("../fonts/NotoSans/NotoSans-Regular.ttf");
+const BOLD_FONT: &[u8] = include_bytes!("../fonts/NotoSans/NotoSans-Bold.ttf");
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderOptions {
+    pub size: u32, // width and height of the image in pixels
+    pub show_solution: bool,
+    pub show_candidates: bool, // pencil marks where the puzzle has them, otherwise every value the rules allow
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions { size: 600, show_solution: false, show_candidates: false }
+    }
+}
+
+pub struct Canvas {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>, // RGB, row by row from the top left
+}
+
+impl Canvas {
+    pub fn new(width: u32, height: u32) -> Canvas {
+        Canvas { width, height, pixels: vec![255; (width * height * 3) as usize] }
+    }
+
+    // Mixes a color into a pixel, `coverage` being how much of the pixel it covers from 0 to 1
+    fn blend(&mut self, x: i64, y: i64, color: (u8, u8, u8), coverage: f64) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 || coverage <= 0.0 {
+            return;
+        }
+        let coverage = coverage.min(1.0);
+        let i = ((y as u32 * self.width + x as u32) * 3) as usize;
+        for (channel, value) in [color.0, color.1, color.2].iter().enumerate() {
+            let old = self.pixels[i + channel] as f64;
+            self.pixels[i + channel] = (old + (*value as f64 - old) * coverage).round() as u8;
+        }
+    }
+
+    // Draws an antialiased line with square ends, like the SVG and PDF exports do
+    pub fn draw_line(&mut self, from: (f64, f64), to: (f64, f64), width: f64, color: (u8, u8, u8)) {
+        let half = width.max(1.0) / 2.0;
+        let length = ((to.0 - from.0).powi(2) + (to.1 - from.1).powi(2)).sqrt();
+        let (ux, uy) = if length == 0.0 {(1.0, 0.0)} else {((to.0 - from.0) / length, (to.1 - from.1) / length)};
+        let min_x = (from.0.min(to.0) - half - 1.0).floor() as i64;
+        let max_x = (from.0.max(to.0) + half + 1.0).ceil() as i64;
+        let min_y = (from.1.min(to.1) - half - 1.0).floor() as i64;
+        let max_y = (from.1.max(to.1) + half + 1.0).ceil() as i64;
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                // position of the pixel center measured along and across the line
+                let (px, py) = (x as f64 + 0.5 - from.0, y as f64 + 0.5 - from.1);
+                let along = px * ux + py * uy;
+                let across = (px * uy - py * ux).abs();
+                let past_end = (-along).max(along - length).max(0.0);
+                self.blend(x, y, color, (half + 0.5 - across).min(half + 0.5 - past_end));
+            }
+        }
+    }
+
+    // Fills the pixels whose centers are inside the rectangle
+    pub fn fill_rect(&mut self, from: (f64, f64), to: (f64, f64), color: (u8, u8, u8)) {
+        for y in (from.1 - 0.5).ceil() as i64..(to.1 - 0.5).ceil() as i64 {
+            for x in (from.0 - 0.5).ceil() as i64..(to.0 - 0.5).ceil() as i64 {
+                self.blend(x, y, color, 1.0);
+            }
+        }
+    }
+
+    // Fills an antialiased circle
+    pub fn fill_circle(&mut self, at: (f64, f64), radius: f64, color: (u8, u8, u8)) {
+        for y in (at.1 - radius - 1.0).floor() as i64..=(at.1 + radius + 1.0).ceil() as i64 {
+            for x in (at.0 - radius - 1.0).floor() as i64..=(at.0 + radius + 1.0).ceil() as i64 {
+                let distance = ((x as f64 + 0.5 - at.0).powi(2) + (y as f64 + 0.5 - at.1).powi(2)).sqrt();
+                self.blend(x, y, color, radius + 0.5 - distance);
+            }
+        }
+    }
+
+    // Draws text centered on `at`, the same way the SVG and PDF exports place it
+    pub fn draw_text(&mut self, at: (f64, f64), size: f64, bold: bool, color: (u8, u8, u8), text: &str) {
+        let font = Font::try_from_bytes(if bold {BOLD_FONT} else {REGULAR_FONT}).expect("the built in fonts are valid");
+        let scale = Scale::uniform(size as f32);
+        let glyphs: Vec<_> = font.layout(text, scale, point(0.0, 0.0)).collect();
+        let width = glyphs.last().map_or(0.0, |g| g.position().x + g.unpositioned().h_metrics().advance_width) as f64;
+        let (left, baseline) = (at.0 - width / 2.0, at.1 + 0.35 * size);
+        for glyph in glyphs {
+            if let Some(bounds) = glyph.pixel_bounding_box() {
+                glyph.draw(|x, y, coverage| {
+                    let px = (left + (bounds.min.x + x as i32) as f64).round() as i64;
+                    let py = (baseline + (bounds.min.y + y as i32) as f64).round() as i64;
+                    self.blend(px, py, color, coverage as f64);
+                });
+            }
+        }
+    }
+
+    pub fn draw_shapes(&mut self, shapes: &[Shape]) {
+        for shape in shapes {
+            match shape {
+                Shape::Line { from, to, width, color } => self.draw_line(*from, *to, *width, *color),
+                Shape::Text { at, size, bold, color, text } => self.draw_text(*at, *size, *bold, *color, text),
+                Shape::Rect { from, to, color } => self.fill_rect(*from, *to, *color),
+                Shape::Circle { at, radius, color } => self.fill_circle(*at, *radius, *color),
+            }
+        }
+    }
+
+    pub fn to_png(&self) -> Result<Vec<u8>, String> {
+        let mut result = Vec::new();
+        let mut encoder = png::Encoder::new(&mut result, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(|e| format!("Could not encode the PNG: {}", e))?;
+        writer.write_image_data(&self.pixels).map_err(|e| format!("Could not encode the PNG: {}", e))?;
+        writer.finish().map_err(|e| format!("Could not encode the PNG: {}", e))?;
+        Ok(result)
+    }
+}
+
+// Draws a puzzle, filling the image apart from a small margin
+pub fn render(puzzle: &Puzzle, options: &RenderOptions) -> Canvas {
+    let n = puzzle.side_length as usize;
+    let values: Vec<Vec<Option<i32>>> = if options.show_solution {
+        puzzle.solve().values
+    } else {
+        (0..n).map(|r| (0..n).map(|c| puzzle.value(r, c)).collect()).collect()
+    };
+    let candidates: Option<Vec<Vec<Vec<i32>>>> = if options.show_candidates {
+        let allowed = puzzle.rule_candidates();
+        Some((0..n).map(|r| (0..n).map(|c| match &allowed {
+            Some(allowed) if puzzle.candidates[r][c].is_empty() => allowed[r][c].clone(),
+            _ => puzzle.candidates[r][c].clone(),
+        }).collect()).collect())
+    } else {
+        None
+    };
+
+    let margin = (options.size as f64 * 0.02).max(2.0);
+    let mut shapes = Vec::new();
+    export::draw_board(&mut shapes, puzzle, &values, candidates.as_deref(), margin, margin, options.size as f64 - 2.0 * margin);
+    let mut canvas = Canvas::new(options.size, options.size);
+    canvas.draw_shapes(&shapes);
+    canvas
+}
+
+pub fn save_png(path: &Path, puzzle: &Puzzle, options: &RenderOptions) -> Result<(), String> {
+    let contents = render(puzzle, options).to_png()?;
+    fs::write(path, contents).map_err(|e| format!("Could not write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::photo;
+
+    // A third of a valid solution as the givens, so that every value shows up
+    fn puzzle(side_length: i32) -> Puzzle {
+        let mut puzzle = Puzzle::new(side_length);
+        let (box_rows, box_cols) = puzzle.box_shape;
+        let n = side_length as usize;
+        for r in 0..n {
+            for c in (0..n).filter(|c| (r + c) % 3 == 0) {
+                let v = (r * box_cols as usize + r / box_rows as usize + c) % n;
+                puzzle.givens[r][c] = Some(v as i32 + 1);
+            }
+        }
+        puzzle
+    }
+
+    #[test]
+    fn png_round_trip() {
+        for side_length in [4, 6, 8, 9, 12, 16] {
+            let puzzle = puzzle(side_length);
+            let canvas = render(&puzzle, &RenderOptions::default());
+            let png = canvas.to_png().unwrap();
+            let mut reader = png::Decoder::new(png.as_slice()).read_info().unwrap();
+            let mut pixels = vec![0; reader.output_buffer_size()];
+            let info = reader.next_frame(&mut pixels).unwrap();
+            assert_eq!((info.width, info.height, info.color_type), (600, 600, png::ColorType::Rgb));
+            assert_eq!(pixels, canvas.pixels);
+
+            let imported = photo::import_image(&png).unwrap();
+            assert_eq!(imported.side_length, side_length);
+            assert_eq!(imported.givens, puzzle.givens, "{}x{} givens", side_length, side_length);
+        }
+    }
+}
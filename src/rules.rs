@@ -0,0 +1,900 @@
+// Rules that the solver checks on top of the rows, columns and regions of every sudoku
+//
+// Each variant (the diagonals of Sudoku X, killer cages, thermometers, ...) is a type implementing Rule, and
+// puzzle::Constraint::rule turns the constraints stored with a puzzle into them. The solver asks every rule on the
+// board whether a value may go in a square, and whether a filled in board keeps to it, so adding a variant doesn't
+// need any changes to solve_board.
+
+use crate::solver::{self, SudokuBoard};
+
+pub trait Rule {
+    // Groups of squares that must all have different values. Groups of side_length squares are also searched for
+    // hidden singles by the solver, like the rows, columns and regions are.
+    fn units(&self) -> Vec<Vec<(usize, usize)>> {
+        Vec::new()
+    }
+
+    // Whether `value` can go in the empty square at (row, col), given the values placed in the other squares so far
+    fn allows(&self, board: &SudokuBoard, value: i32, row: usize, col: usize) -> bool;
+
+    // Whether a completely filled in board keeps to the rule
+    fn is_satisfied(&self, board: &SudokuBoard) -> bool;
+
+    // Squares whose possible values the rule ties to the value at (row, col), like the squares next to it for
+    // non-consecutive. The solver narrows their values down again whenever a value is placed at (row, col).
+    fn linked_squares(&self, _side_length: usize, _row: usize, _col: usize) -> Vec<(usize, usize)> {
+        Vec::new()
+    }
+}
+
+// Groups of squares that can't repeat a value, like the diagonals of Sudoku X
+pub struct AllDifferent {
+    pub units: Vec<Vec<(usize, usize)>>,
+}
+
+impl Rule for AllDifferent {
+    fn units(&self) -> Vec<Vec<(usize, usize)>> {
+        self.units.clone()
+    }
+
+    fn allows(&self, board: &SudokuBoard, value: i32, row: usize, col: usize) -> bool {
+        self.units.iter()
+            .filter(|unit| unit.contains(&(row, col)))
+            .all(|unit| unit.iter().all(|&(r, c)| (r, c) == (row, col) || board.values[r][c] != Some(value)))
+    }
+
+    fn is_satisfied(&self, board: &SudokuBoard) -> bool {
+        self.units.iter().all(|unit| {
+            let mut seen: Vec<i32> = unit.iter().filter_map(|&(r, c)| board.values[r][c]).collect();
+            seen.sort();
+            seen.dedup();
+            seen.len() == unit.len()
+        })
+    }
+
+    fn linked_squares(&self, _side_length: usize, row: usize, col: usize) -> Vec<(usize, usize)> {
+        self.units.iter().flat_map(|unit| others(unit, row, col)).collect()
+    }
+}
+
+// The squares of both main diagonals, top left to bottom right first
+pub fn diagonal_units(side_length: i32) -> Vec<Vec<(usize, usize)>> {
+    let n = side_length as usize;
+    vec![(0..n).map(|i| (i, i)).collect(), (0..n).map(|i| (i, n - 1 - i)).collect()]
+}
+
+// The extra boxes of Windoku (Hyper Sudoku): box sized windows one square in from the edges, with one row (or column)
+// of squares between neighbouring windows, so a 9x9 board has four of them
+pub fn window_units(side_length: i32, box_shape: (i32, i32)) -> Vec<Vec<(usize, usize)>> {
+    let (box_rows, box_cols) = (box_shape.0 as usize, box_shape.1 as usize);
+    let n = side_length as usize;
+    let mut units = Vec::new();
+    for top in (1..).step_by(box_rows + 1).take_while(|top| top + box_rows < n) {
+        for left in (1..).step_by(box_cols + 1).take_while(|left| left + box_cols < n) {
+            units.push((top..top + box_rows).flat_map(|r| (left..left + box_cols).map(move |c| (r, c))).collect());
+        }
+    }
+    units
+}
+
+pub const KNIGHT_MOVES: [(i64, i64); 8] = [(-2, -1), (-2, 1), (-1, -2), (-1, 2), (1, -2), (1, 2), (2, -1), (2, 1)];
+pub const KING_MOVES: [(i64, i64); 8] = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
+pub const ORTHOGONAL_MOVES: [(i64, i64); 4] = [(-1, 0), (0, -1), (0, 1), (1, 0)];
+
+// The squares of `cells` other than (row, col), if it is one of them
+fn others(cells: &[(usize, usize)], row: usize, col: usize) -> Vec<(usize, usize)> {
+    if cells.contains(&(row, col)) {
+        cells.iter().filter(|&&cell| cell != (row, col)).copied().collect()
+    } else {
+        Vec::new()
+    }
+}
+
+// The squares of an n x n board one of `moves` away from (row, col)
+fn neighbours(n: usize, row: usize, col: usize, moves: &[(i64, i64)]) -> Vec<(usize, usize)> {
+    moves.iter()
+        .map(|&(dr, dc)| (row as i64 + dr, col as i64 + dc))
+        .filter(|&(r, c)| r >= 0 && c >= 0 && r < n as i64 && c < n as i64)
+        .map(|(r, c)| (r as usize, c as usize))
+        .collect()
+}
+
+// Chess constraints: squares one move of a chess piece apart (given as (row, col) offsets) can't have the same value
+pub struct NoRepeatsApart {
+    pub moves: Vec<(i64, i64)>,
+}
+
+impl Rule for NoRepeatsApart {
+    fn allows(&self, board: &SudokuBoard, value: i32, row: usize, col: usize) -> bool {
+        neighbours(board.values.len(), row, col, &self.moves).iter().all(|&(r, c)| board.values[r][c] != Some(value))
+    }
+
+    fn is_satisfied(&self, board: &SudokuBoard) -> bool {
+        let n = board.values.len();
+        (0..n).all(|row| (0..n).all(|col| {
+            board.values[row][col].is_none() || neighbours(n, row, col, &self.moves).iter().all(|&(r, c)| board.values[r][c] != board.values[row][col])
+        }))
+    }
+
+    fn linked_squares(&self, side_length: usize, row: usize, col: usize) -> Vec<(usize, usize)> {
+        neighbours(side_length, row, col, &self.moves)
+    }
+}
+
+// Squares next to each other (not diagonally) can't have consecutive values
+pub struct NonConsecutive;
+
+impl Rule for NonConsecutive {
+    fn allows(&self, board: &SudokuBoard, value: i32, row: usize, col: usize) -> bool {
+        // an empty neighbour also needs a value left that isn't next to this one, so a square can't be a 4 when the
+        // square beside it can only be a 3 or a 5
+        let n = board.values.len() as i32;
+        neighbours(board.values.len(), row, col, &ORTHOGONAL_MOVES).iter().all(|&(r, c)| match board.values[r][c] {
+            Some(v) => (v - value).abs() != 1,
+            None => (1..=n).any(|w| (w - value).abs() > 1 && free_for(board, w, r, c)),
+        })
+    }
+
+    fn is_satisfied(&self, board: &SudokuBoard) -> bool {
+        let n = board.values.len();
+        (0..n).all(|row| (0..n).all(|col| match board.values[row][col] {
+            Some(value) => self.allows(board, value, row, col),
+            None => true,
+        }))
+    }
+
+    fn linked_squares(&self, side_length: usize, row: usize, col: usize) -> Vec<(usize, usize)> {
+        neighbours(side_length, row, col, &ORTHOGONAL_MOVES)
+    }
+}
+
+// Thermometers: the values go up (strictly) from the bulb, which is the first square, to the other end
+pub struct Thermometer {
+    pub cells: Vec<(usize, usize)>,
+}
+
+impl Rule for Thermometer {
+    fn units(&self) -> Vec<Vec<(usize, usize)>> {
+        vec![self.cells.clone()]
+    }
+
+    fn allows(&self, board: &SudokuBoard, value: i32, row: usize, col: usize) -> bool {
+        let position = match self.cells.iter().position(|&cell| cell == (row, col)) {
+            Some(position) => position,
+            None => return true,
+        };
+        // there has to be room for the squares below it to go down to 1, and the squares above it to go up to side_length
+        let side_length = board.values.len() as i32;
+        if value <= position as i32 || value > side_length - (self.cells.len() - 1 - position) as i32 {
+            return false;
+        }
+        // and room between it and every value already placed on the thermometer
+        self.cells.iter().enumerate().all(|(i, &(r, c))| match board.values[r][c] {
+            Some(placed) if i < position => value - placed >= (position - i) as i32,
+            Some(placed) if i > position => placed - value >= (i - position) as i32,
+            _ => true,
+        })
+    }
+
+    fn is_satisfied(&self, board: &SudokuBoard) -> bool {
+        self.cells.windows(2).all(|pair| {
+            let ((r1, c1), (r2, c2)) = (pair[0], pair[1]);
+            board.values[r1][c1] < board.values[r2][c2]
+        })
+    }
+
+    fn linked_squares(&self, _side_length: usize, row: usize, col: usize) -> Vec<(usize, usize)> {
+        others(&self.cells, row, col)
+    }
+}
+
+// Arrows: the values along the shaft add up to the number in the circle. A circle of more than one square (a pill)
+// is read as a base 10 number with one digit per square, the first square being the most significant, so pills
+// are only allowed on boards of up to 9x9 (see Puzzle::check_constraints).
+pub struct Arrow {
+    pub circle: Vec<(usize, usize)>,
+    pub cells: Vec<(usize, usize)>,
+}
+
+impl Arrow {
+    // The smallest and largest values the shaft could add up to, and the circle could be, with `value` at (row, col)
+    fn bounds(&self, board: &SudokuBoard, value: i32, row: usize, col: usize) -> ((i64, i64), (i64, i64)) {
+        let side_length = board.values.len() as i64;
+        let value_at = |r: usize, c: usize| if (r, c) == (row, col) {Some(value)} else {board.values[r][c]};
+        let mut shaft = (0, 0);
+        for &(r, c) in &self.cells {
+            let (low, high) = value_at(r, c).map_or((1, side_length), |v| (v as i64, v as i64));
+            shaft = (shaft.0 + low, shaft.1 + high);
+        }
+        let mut circle = (0, 0);
+        for &(r, c) in &self.circle {
+            let (low, high) = value_at(r, c).map_or((1, side_length), |v| (v as i64, v as i64));
+            circle = (circle.0 * 10 + low, circle.1 * 10 + high);
+        }
+        (shaft, circle)
+    }
+}
+
+impl Rule for Arrow {
+    fn allows(&self, board: &SudokuBoard, value: i32, row: usize, col: usize) -> bool {
+        if !self.circle.contains(&(row, col)) && !self.cells.contains(&(row, col)) {
+            return true;
+        }
+        // the range of sums the shaft could still make has to overlap the range of numbers the circle could still be
+        let (shaft, circle) = self.bounds(board, value, row, col);
+        shaft.0 <= circle.1 && circle.0 <= shaft.1
+    }
+
+    fn is_satisfied(&self, board: &SudokuBoard) -> bool {
+        let (row, col) = self.circle[0];
+        match board.values[row][col] {
+            Some(value) => {
+                let (shaft, circle) = self.bounds(board, value, row, col);
+                shaft.0 == shaft.1 && shaft == circle
+            }
+            None => false,
+        }
+    }
+
+    fn linked_squares(&self, _side_length: usize, row: usize, col: usize) -> Vec<(usize, usize)> {
+        others(&[self.circle.clone(), self.cells.clone()].concat(), row, col)
+    }
+}
+
+// Whether `value` could go in the empty square at (row, col) as far as its row, column and region go
+fn free_for(board: &SudokuBoard, value: i32, row: usize, col: usize) -> bool {
+    let v = (value - 1) as usize;
+    board.values[row][col].is_none() && !board.val_in_row[v][row] && !board.val_in_col[v][col] && !board.val_in_box[v][board.regions[row][col]]
+}
+
+// A relation between the values of two squares next to each other, like the dots of Kropki sudoku
+pub struct Pair {
+    pub cells: [(usize, usize); 2],
+    pub holds: fn(i32, i32) -> bool, // called with the first square's value, then the second's
+}
+
+impl Rule for Pair {
+    fn allows(&self, board: &SudokuBoard, value: i32, row: usize, col: usize) -> bool {
+        let (other, is_first) = if self.cells[0] == (row, col) {
+            (self.cells[1], true)
+        } else if self.cells[1] == (row, col) {
+            (self.cells[0], false)
+        } else {
+            return true;
+        };
+        let holds = |other: i32| if is_first {(self.holds)(value, other)} else {(self.holds)(other, value)};
+        // either the other square's value fits, or it is empty and some value that could still go there would
+        let side_length = board.values.len() as i32;
+        match board.values[other.0][other.1] {
+            Some(placed) => holds(placed),
+            None => (1..=side_length).any(|w| w != value && holds(w) && free_for(board, w, other.0, other.1)),
+        }
+    }
+
+    fn is_satisfied(&self, board: &SudokuBoard) -> bool {
+        let [(r1, c1), (r2, c2)] = self.cells;
+        match (board.values[r1][c1], board.values[r2][c2]) {
+            (Some(a), Some(b)) => (self.holds)(a, b),
+            _ => false,
+        }
+    }
+
+    fn linked_squares(&self, _side_length: usize, row: usize, col: usize) -> Vec<(usize, usize)> {
+        others(&self.cells, row, col)
+    }
+}
+
+// The negative constraint of Kropki and XV puzzles: squares next to each other without a marker between them can't
+// have values that any of the relations hold for
+pub struct NoPair {
+    pub marked: Vec<[(usize, usize); 2]>, // pairs that have a marker, with the top left square first
+    pub relations: Vec<fn(i32, i32) -> bool>,
+}
+
+impl NoPair {
+    fn breaks(&self, a: i32, b: i32) -> bool {
+        self.relations.iter().any(|holds| holds(a, b) || holds(b, a))
+    }
+}
+
+impl Rule for NoPair {
+    fn allows(&self, board: &SudokuBoard, value: i32, row: usize, col: usize) -> bool {
+        neighbours(board.values.len(), row, col, &ORTHOGONAL_MOVES).iter().all(|&(r, c)| {
+            let pair = if (r, c) < (row, col) {[(r, c), (row, col)]} else {[(row, col), (r, c)]};
+            // an empty neighbour also needs a value left that the relations don't hold for
+            self.marked.contains(&pair) || match board.values[r][c] {
+                Some(other) => !self.breaks(value, other),
+                None => (1..=board.values.len() as i32).any(|w| w != value && !self.breaks(value, w) && free_for(board, w, r, c)),
+            }
+        })
+    }
+
+    fn is_satisfied(&self, board: &SudokuBoard) -> bool {
+        let n = board.values.len();
+        (0..n).all(|row| (0..n).all(|col| match board.values[row][col] {
+            Some(value) => self.allows(board, value, row, col),
+            None => true,
+        }))
+    }
+
+    fn linked_squares(&self, side_length: usize, row: usize, col: usize) -> Vec<(usize, usize)> {
+        neighbours(side_length, row, col, &ORTHOGONAL_MOVES)
+    }
+}
+
+// The most sets of values a cage will list. Big cages on big boards could have millions, so they are checked against
+// the smallest and largest sums their empty squares could still make instead.
+const MAX_COMBINATIONS: u64 = 1 << 16;
+
+// Killer sudoku cages: the squares of a cage can't repeat a value, and add up to its sum
+pub struct Cage {
+    pub cells: Vec<(usize, usize)>,
+    pub sum: i32,
+    combinations: Option<Vec<u64>>, // every set of values that could fill the cage, as bits (value 1 is bit 0)
+}
+
+impl Cage {
+    pub fn new(cells: Vec<(usize, usize)>, sum: i32, side_length: i32) -> Cage {
+        let combinations = (choose(side_length as u64, cells.len() as u64) <= MAX_COMBINATIONS)
+            .then(|| sum_combinations(cells.len(), sum, side_length));
+        Cage { cells, sum, combinations }
+    }
+
+    // Whether the empty squares could still make up the rest of the sum with different values that aren't used yet
+    fn within_bounds(&self, used: u64, side_length: i32) -> bool {
+        let unused: Vec<i32> = (1..=side_length).filter(|v| used & 1 << (v - 1) == 0).collect();
+        let empty = self.cells.len() - used.count_ones() as usize;
+        let rest = self.sum - (1..=side_length).filter(|v| used & 1 << (v - 1) != 0).sum::<i32>();
+        empty <= unused.len()
+            && unused[..empty].iter().sum::<i32>() <= rest
+            && rest <= unused[unused.len() - empty..].iter().sum::<i32>()
+    }
+}
+
+// The number of ways to pick k things out of n, stopping once it is past MAX_COMBINATIONS
+fn choose(n: u64, k: u64) -> u64 {
+    let mut result = 1;
+    for i in 0..k.min(n.saturating_sub(k)) {
+        result = result * (n - i) / (i + 1);
+        if result > MAX_COMBINATIONS {
+            break;
+        }
+    }
+    if k > n {0} else {result}
+}
+
+impl Rule for Cage {
+    fn units(&self) -> Vec<Vec<(usize, usize)>> {
+        vec![self.cells.clone()]
+    }
+
+    fn allows(&self, board: &SudokuBoard, value: i32, row: usize, col: usize) -> bool {
+        if !self.cells.contains(&(row, col)) {
+            return true;
+        }
+        let mut used: u64 = 1 << (value - 1);
+        for &(r, c) in &self.cells {
+            if let Some(v) = board.values[r][c] {
+                if (r, c) != (row, col) && v == value {
+                    return false;
+                }
+                used |= 1 << (v - 1);
+            }
+        }
+        // the values placed so far, along with this one, have to be part of some set of values that adds up to the sum
+        match &self.combinations {
+            Some(combinations) => combinations.iter().any(|combination| combination & used == used),
+            None => self.within_bounds(used, board.values.len() as i32),
+        }
+    }
+
+    fn is_satisfied(&self, board: &SudokuBoard) -> bool {
+        let values: Vec<i32> = self.cells.iter().filter_map(|&(r, c)| board.values[r][c]).collect();
+        let mut distinct = values.clone();
+        distinct.sort();
+        distinct.dedup();
+        distinct.len() == self.cells.len() && values.iter().sum::<i32>() == self.sum
+    }
+
+    fn linked_squares(&self, _side_length: usize, row: usize, col: usize) -> Vec<(usize, usize)> {
+        others(&self.cells, row, col)
+    }
+}
+
+// Whether `count` different values from 1 to side_length can add up to `sum`. Swapping one value for the next one up
+// raises the sum by one, so every sum from the smallest to the largest can be made.
+pub fn sum_is_possible(count: usize, sum: i32, side_length: i32) -> bool {
+    let count = count as i32;
+    count <= side_length && count * (count + 1) / 2 <= sum && sum <= count * side_length - count * (count - 1) / 2
+}
+
+// All sets of `count` different values from 1 to side_length that add up to `sum`, as bits (value 1 is bit 0)
+pub fn sum_combinations(count: usize, sum: i32, side_length: i32) -> Vec<u64> {
+    let mut combinations = Vec::new();
+    add_combinations(&mut combinations, 0, 1, count as i32, sum, side_length);
+    combinations
+}
+
+// Adds the sets that extend `chosen` with `count` more values of at least `lowest`
+fn add_combinations(combinations: &mut Vec<u64>, chosen: u64, lowest: i32, count: i32, sum: i32, side_length: i32) {
+    if count == 0 {
+        if sum == 0 {
+            combinations.push(chosen);
+        }
+        return;
+    }
+    // the smallest and largest sums that `count` more values could still make
+    let min_sum = count * lowest + count * (count - 1) / 2;
+    let max_sum = count * side_length - count * (count - 1) / 2;
+    if sum < min_sum || sum > max_sum {
+        return;
+    }
+    for v in lowest..=side_length {
+        add_combinations(combinations, chosen | 1 << (v - 1), v + 1, count - 1, sum - v, side_length);
+    }
+}
+
+// Sandwich sums: the values between the 1 and the side_length of a row or column add up to the clue outside it
+pub struct Sandwich {
+    pub cells: Vec<(usize, usize)>,
+    pub sum: i32,
+}
+
+impl Sandwich {
+    // Whether the squares between positions `a` and `b` of the line could still add up to the sum
+    fn fits(&self, values: &[Option<i32>], a: usize, b: usize) -> bool {
+        let side_length = values.len() as i32;
+        let between = &values[a.min(b) + 1..a.max(b)];
+        let placed: i32 = between.iter().flatten().sum();
+        let empty = between.iter().filter(|v| v.is_none()).count();
+        // the empty squares between get different values from 2 to side_length - 1 that aren't in the line yet
+        let unused: Vec<i32> = (2..side_length).filter(|v| !values.contains(&Some(*v))).collect();
+        if unused.len() < empty {
+            return false;
+        }
+        let low: i32 = unused[..empty].iter().sum();
+        let high: i32 = unused[unused.len() - empty..].iter().sum();
+        placed + low <= self.sum && self.sum <= placed + high
+    }
+}
+
+impl Rule for Sandwich {
+    fn allows(&self, board: &SudokuBoard, value: i32, row: usize, col: usize) -> bool {
+        let position = match self.cells.iter().position(|&cell| cell == (row, col)) {
+            Some(position) => position,
+            None => return true,
+        };
+        let side_length = self.cells.len() as i32;
+        let values: Vec<Option<i32>> = self.cells.iter().enumerate()
+            .map(|(i, &(r, c))| if i == position {Some(value)} else {board.values[r][c]})
+            .collect();
+        // where the 1 and the side_length are, or could still go
+        let ends = |v: i32| match values.iter().position(|&placed| placed == Some(v)) {
+            Some(i) => vec![i],
+            None => (0..values.len()).filter(|&i| values[i].is_none() && free_for(board, v, self.cells[i].0, self.cells[i].1)).collect(),
+        };
+        let (ones, highest) = (ends(1), ends(side_length));
+        ones.iter().any(|&a| highest.iter().any(|&b| a != b && self.fits(&values, a, b)))
+    }
+
+    fn is_satisfied(&self, board: &SudokuBoard) -> bool {
+        let values: Vec<Option<i32>> = self.cells.iter().map(|&(r, c)| board.values[r][c]).collect();
+        let find = |v: i32| values.iter().position(|&placed| placed == Some(v));
+        match (find(1), find(self.cells.len() as i32)) {
+            (Some(a), Some(b)) => values[a.min(b) + 1..a.max(b)].iter().map(|v| v.unwrap_or(0)).sum::<i32>() == self.sum,
+            _ => false,
+        }
+    }
+
+    fn linked_squares(&self, _side_length: usize, row: usize, col: usize) -> Vec<(usize, usize)> {
+        others(&self.cells, row, col)
+    }
+}
+
+
+// Little killer sums: the values along a diagonal add up to the clue outside the grid. Unlike a cage, values can
+// repeat along the diagonal.
+pub struct LittleKiller {
+    pub cells: Vec<(usize, usize)>,
+    pub sum: i32,
+}
+
+impl Rule for LittleKiller {
+    fn allows(&self, board: &SudokuBoard, value: i32, row: usize, col: usize) -> bool {
+        if !self.cells.contains(&(row, col)) {
+            return true;
+        }
+        let side_length = board.values.len() as i32;
+        let mut low = 0;
+        let mut high = 0;
+        for &(r, c) in &self.cells {
+            let placed = if (r, c) == (row, col) {Some(value)} else {board.values[r][c]};
+            let (l, h) = placed.map_or((1, side_length), |v| (v, v));
+            low += l;
+            high += h;
+        }
+        low <= self.sum && self.sum <= high
+    }
+
+    fn is_satisfied(&self, board: &SudokuBoard) -> bool {
+        self.cells.iter().map(|&(r, c)| board.values[r][c].unwrap_or(0)).sum::<i32>() == self.sum
+    }
+
+    fn linked_squares(&self, _side_length: usize, row: usize, col: usize) -> Vec<(usize, usize)> {
+        others(&self.cells, row, col)
+    }
+}
+
+// German whispers: squares next to each other along the line differ by at least `gap` (5 on a 9x9 board)
+pub struct Whisper {
+    pub cells: Vec<(usize, usize)>,
+    pub gap: i32,
+}
+
+impl Rule for Whisper {
+    fn allows(&self, board: &SudokuBoard, value: i32, row: usize, col: usize) -> bool {
+        let side_length = board.values.len() as i32;
+        // every neighbour along the line either has a value far enough away, or could still get one
+        self.cells.iter().enumerate().filter(|&(_, &cell)| cell == (row, col)).all(|(i, _)| {
+            let before = i.checked_sub(1).map(|j| self.cells[j]);
+            let after = self.cells.get(i + 1).copied();
+            [before, after].into_iter().flatten().all(|(r, c)| match board.values[r][c] {
+                Some(placed) => (placed - value).abs() >= self.gap,
+                None => (1..=side_length).any(|w| (w - value).abs() >= self.gap && free_for(board, w, r, c)),
+            })
+        })
+    }
+
+    fn is_satisfied(&self, board: &SudokuBoard) -> bool {
+        self.cells.windows(2).all(|pair| match (board.values[pair[0].0][pair[0].1], board.values[pair[1].0][pair[1].1]) {
+            (Some(a), Some(b)) => (a - b).abs() >= self.gap,
+            _ => false,
+        })
+    }
+
+    fn linked_squares(&self, _side_length: usize, row: usize, col: usize) -> Vec<(usize, usize)> {
+        others(&self.cells, row, col)
+    }
+}
+
+// Renban lines: the squares have different values that make a run of consecutive numbers, in any order
+pub struct Renban {
+    pub cells: Vec<(usize, usize)>,
+}
+
+impl Rule for Renban {
+    fn units(&self) -> Vec<Vec<(usize, usize)>> {
+        vec![self.cells.clone()]
+    }
+
+    fn allows(&self, board: &SudokuBoard, value: i32, row: usize, col: usize) -> bool {
+        if !self.cells.contains(&(row, col)) {
+            return true;
+        }
+        let mut low = value;
+        let mut high = value;
+        for &(r, c) in &self.cells {
+            if let Some(placed) = board.values[r][c] {
+                if (r, c) != (row, col) && placed == value {
+                    return false;
+                }
+                low = low.min(placed);
+                high = high.max(placed);
+            }
+        }
+        // the run has one value per square, so the values so far can't be further apart than that
+        high - low < self.cells.len() as i32
+    }
+
+    fn is_satisfied(&self, board: &SudokuBoard) -> bool {
+        let mut values: Vec<i32> = self.cells.iter().filter_map(|&(r, c)| board.values[r][c]).collect();
+        values.sort();
+        values.len() == self.cells.len() && values.windows(2).all(|pair| pair[1] == pair[0] + 1)
+    }
+
+    fn linked_squares(&self, _side_length: usize, row: usize, col: usize) -> Vec<(usize, usize)> {
+        others(&self.cells, row, col)
+    }
+}
+
+// Palindrome lines: the line reads the same from either end
+pub struct Palindrome {
+    pub cells: Vec<(usize, usize)>,
+}
+
+impl Rule for Palindrome {
+    fn allows(&self, board: &SudokuBoard, value: i32, row: usize, col: usize) -> bool {
+        let last = self.cells.len().saturating_sub(1);
+        self.cells.iter().enumerate().filter(|&(_, &cell)| cell == (row, col)).all(|(i, _)| {
+            let (r, c) = self.cells[last - i];
+            (r, c) == (row, col) || match board.values[r][c] {
+                Some(placed) => placed == value,
+                // the mirrored square needs the same value, so it can't share a row, column, region or any other
+                // unit the solver knows about with this one
+                None => free_for(board, value, r, c) && !solver::full_units(board, board.values.len() as i32).iter()
+                    .any(|unit| unit.contains(&(r, c)) && unit.contains(&(row, col))),
+            }
+        })
+    }
+
+    fn is_satisfied(&self, board: &SudokuBoard) -> bool {
+        let values: Vec<Option<i32>> = self.cells.iter().map(|&(r, c)| board.values[r][c]).collect();
+        values.iter().all(|v| v.is_some()) && values.iter().eq(values.iter().rev())
+    }
+
+    fn linked_squares(&self, _side_length: usize, row: usize, col: usize) -> Vec<(usize, usize)> {
+        others(&self.cells, row, col)
+    }
+}
+
+// Odd/even squares: the square's value has to be even, or odd
+pub struct Parity {
+    pub cell: (usize, usize),
+    pub even: bool,
+}
+
+impl Rule for Parity {
+    fn allows(&self, _board: &SudokuBoard, value: i32, row: usize, col: usize) -> bool {
+        (row, col) != self.cell || (value % 2 == 0) == self.even
+    }
+
+    fn is_satisfied(&self, board: &SudokuBoard) -> bool {
+        board.values[self.cell.0][self.cell.1].is_some_and(|value| (value % 2 == 0) == self.even)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::puzzle::EdgeMarker;
+
+    // A board with the values placed, each given as (row, col, value)
+    fn board(side_length: i32, values: &[(usize, usize, i32)]) -> SudokuBoard {
+        let shape = solver::default_box_shape(side_length).unwrap();
+        let mut board = solver::new_board(side_length, solver::box_regions(side_length, shape));
+        for &(r, c, v) in values {
+            assert!(solver::update_board(&mut board, v, r, c));
+        }
+        board
+    }
+
+    // A board filled in from one string of digits per row
+    fn filled(rows: &[&str]) -> SudokuBoard {
+        let values: Vec<(usize, usize, i32)> = rows.iter().enumerate()
+            .flat_map(|(r, row)| row.chars().enumerate().map(move |(c, digit)| (r, c, digit.to_digit(10).unwrap() as i32)))
+            .collect();
+        board(rows.len() as i32, &values)
+    }
+
+    // A 4x4 solution whose diagonals also have every value once
+    const X_SOLUTION: [&str; 4] = ["1234", "3412", "4321", "2143"];
+
+    #[test]
+    fn non_consecutive() {
+        let board = board(9, &[(4, 4, 5)]);
+        assert!(!NonConsecutive.allows(&board, 4, 4, 5) && !NonConsecutive.allows(&board, 6, 3, 4));
+        assert!(NonConsecutive.allows(&board, 7, 4, 5));
+        assert!(NonConsecutive.allows(&board, 4, 5, 5)); // diagonally next to it
+        // the rest of row 0 and a 4 in column 1 leave (0, 1) with only 3 or 5, so (0, 0) can't be a 4
+        let board = self::board(9, &[(0, 3, 1), (0, 4, 2), (0, 5, 6), (0, 6, 7), (0, 7, 8), (0, 8, 9), (3, 1, 4)]);
+        assert!(!NonConsecutive.allows(&board, 4, 0, 0));
+        assert!(NonConsecutive.allows(&board, 3, 0, 0));
+        assert!(NonConsecutive.is_satisfied(&self::board(9, &[(0, 0, 1), (0, 1, 3)])));
+        assert!(!NonConsecutive.is_satisfied(&self::board(9, &[(0, 0, 1), (0, 1, 2)])));
+    }
+
+    #[test]
+    fn diagonals() {
+        let diagonals = AllDifferent { units: diagonal_units(9) };
+        let board = board(9, &[(0, 0, 5)]);
+        assert!(!diagonals.allows(&board, 5, 4, 4));
+        assert!(diagonals.allows(&board, 5, 2, 6)); // on the other diagonal
+        assert!(diagonals.allows(&board, 5, 4, 3)); // on neither
+        assert!(AllDifferent { units: diagonal_units(4) }.is_satisfied(&filled(&X_SOLUTION)));
+        assert!(!AllDifferent { units: diagonal_units(4) }.is_satisfied(&filled(&["1234", "3412", "2143", "4321"])));
+    }
+
+    #[test]
+    fn windows() {
+        let windows = window_units(9, (3, 3));
+        assert_eq!(windows.len(), 4);
+        assert!(windows[0].contains(&(1, 1)) && windows[0].contains(&(3, 3)) && windows[3].contains(&(7, 7)));
+        let windows = AllDifferent { units: windows };
+        let board = board(9, &[(1, 1, 4)]);
+        assert!(!windows.allows(&board, 4, 3, 3));
+        assert!(windows.allows(&board, 4, 4, 4)); // between the windows
+    }
+
+    #[test]
+    fn no_repeats_apart() {
+        let knight = NoRepeatsApart { moves: KNIGHT_MOVES.to_vec() };
+        let king = NoRepeatsApart { moves: KING_MOVES.to_vec() };
+        let board = board(9, &[(4, 4, 3)]);
+        assert!(!knight.allows(&board, 3, 2, 3) && !knight.allows(&board, 3, 6, 5));
+        assert!(knight.allows(&board, 3, 3, 3) && knight.allows(&board, 4, 2, 3));
+        assert!(!king.allows(&board, 3, 3, 3) && king.allows(&board, 3, 2, 3));
+        assert!(knight.is_satisfied(&self::board(9, &[(0, 0, 1), (1, 2, 2)])));
+        assert!(!knight.is_satisfied(&filled(&X_SOLUTION))); // (0, 0) and (1, 2) are both 1
+        assert!(!king.is_satisfied(&filled(&X_SOLUTION))); // (1, 1) and (2, 0) are both 4
+    }
+
+    #[test]
+    fn thermometer() {
+        let thermometer = Thermometer { cells: vec![(0, 0), (0, 1), (0, 2)] };
+        let empty = board(9, &[]);
+        assert!(!thermometer.allows(&empty, 1, 0, 1) && !thermometer.allows(&empty, 9, 0, 1)); // no room below or above
+        assert!(thermometer.allows(&empty, 2, 0, 1) && thermometer.allows(&empty, 8, 0, 1));
+        let board = board(9, &[(0, 2, 5)]);
+        assert!(thermometer.allows(&board, 4, 0, 1));
+        assert!(!thermometer.allows(&board, 5, 0, 1) && !thermometer.allows(&board, 4, 0, 0));
+        assert!(thermometer.is_satisfied(&self::board(9, &[(0, 0, 1), (0, 1, 4), (0, 2, 9)])));
+        assert!(!thermometer.is_satisfied(&self::board(9, &[(0, 0, 4), (0, 1, 1), (0, 2, 9)])));
+    }
+
+    #[test]
+    fn arrow() {
+        let arrow = Arrow { circle: vec![(0, 0)], cells: vec![(1, 1), (2, 2)] };
+        let board = board(9, &[(0, 0, 5)]);
+        assert!(arrow.allows(&board, 4, 1, 1));
+        assert!(!arrow.allows(&board, 5, 1, 1)); // the other square adds at least 1
+        assert!(arrow.is_satisfied(&self::board(9, &[(0, 0, 5), (1, 1, 2), (2, 2, 3)])));
+        assert!(!arrow.is_satisfied(&self::board(9, &[(0, 0, 5), (1, 1, 2), (2, 2, 4)])));
+
+        // a pill is read as a two digit number, so 20 or more is out of reach of two squares
+        let pill = Arrow { circle: vec![(0, 0), (0, 1)], cells: vec![(1, 0), (2, 0)] };
+        let empty = self::board(9, &[]);
+        assert!(pill.allows(&empty, 1, 0, 0));
+        assert!(!pill.allows(&empty, 2, 0, 0));
+        assert!(pill.is_satisfied(&self::board(9, &[(0, 0, 1), (0, 1, 2), (1, 0, 5), (2, 0, 7)])));
+    }
+
+    #[test]
+    fn pair() {
+        let black = Pair { cells: [(0, 0), (0, 1)], holds: EdgeMarker::BlackDot.relation() };
+        let board = board(9, &[(0, 0, 3)]);
+        assert!(black.allows(&board, 6, 0, 1));
+        assert!(!black.allows(&board, 5, 0, 1));
+        let empty = self::board(9, &[]);
+        assert!(black.allows(&empty, 4, 0, 0));
+        assert!(!black.allows(&empty, 5, 0, 0)); // nothing could go on the other side
+        assert!(black.is_satisfied(&self::board(9, &[(0, 0, 3), (0, 1, 6)])));
+        assert!(!black.is_satisfied(&board));
+    }
+
+    #[test]
+    fn no_pair() {
+        let dots = NoPair {
+            marked: vec![[(0, 0), (0, 1)]],
+            relations: vec![EdgeMarker::WhiteDot.relation(), EdgeMarker::BlackDot.relation()],
+        };
+        let board = board(9, &[(0, 0, 3)]);
+        assert!(dots.allows(&board, 4, 0, 1)); // there is a dot between them
+        assert!(!dots.allows(&board, 4, 1, 0) && !dots.allows(&board, 6, 1, 0));
+        assert!(dots.allows(&board, 8, 1, 0));
+        assert!(dots.is_satisfied(&self::board(9, &[(0, 0, 3), (0, 1, 4), (1, 0, 8)])));
+        assert!(!dots.is_satisfied(&self::board(9, &[(0, 0, 3), (1, 0, 6)])));
+    }
+
+    #[test]
+    fn less() {
+        // the first square is the smaller one
+        let less = Pair { cells: [(0, 0), (0, 1)], holds: EdgeMarker::Less.relation() };
+        let empty = board(9, &[]);
+        assert!(less.allows(&empty, 8, 0, 0) && !less.allows(&empty, 9, 0, 0));
+        assert!(less.allows(&empty, 2, 0, 1) && !less.allows(&empty, 1, 0, 1));
+        assert!(less.is_satisfied(&board(9, &[(0, 0, 2), (0, 1, 7)])));
+        assert!(!less.is_satisfied(&board(9, &[(0, 0, 7), (0, 1, 2)])));
+    }
+
+    #[test]
+    fn sandwich() {
+        let row: Vec<(usize, usize)> = (0..9).map(|c| (0, c)).collect();
+        let sandwich = Sandwich { cells: row.clone(), sum: 5 };
+        let board = board(9, &[(0, 0, 1)]);
+        assert!(sandwich.allows(&board, 9, 0, 2) && sandwich.allows(&board, 9, 0, 3)); // 5, or 2 + 3
+        assert!(!sandwich.allows(&board, 9, 0, 1)); // nothing between them
+        assert!(!sandwich.allows(&board, 9, 0, 8)); // at least 2 + 3 + ... + 8 between them
+        let full = self::board(9, &[(0, 0, 1), (0, 1, 2), (0, 2, 3), (0, 3, 9), (0, 4, 4), (0, 5, 5), (0, 6, 6), (0, 7, 7), (0, 8, 8)]);
+        assert!(sandwich.is_satisfied(&full));
+        assert!(!Sandwich { cells: row, sum: 6 }.is_satisfied(&full));
+    }
+
+    #[test]
+    fn little_killer() {
+        let little_killer = LittleKiller { cells: vec![(0, 2), (1, 1), (2, 0)], sum: 6 };
+        let board = board(9, &[(0, 2, 1)]);
+        assert!(little_killer.allows(&board, 1, 1, 1));
+        assert!(!little_killer.allows(&board, 5, 1, 1));
+        // values can repeat along the diagonal
+        let little_killer = LittleKiller { cells: vec![(2, 3), (3, 2)], sum: 4 };
+        assert!(little_killer.is_satisfied(&self::board(9, &[(2, 3, 2), (3, 2, 2)])));
+        assert!(!little_killer.is_satisfied(&self::board(9, &[(2, 3, 2), (3, 2, 3)])));
+    }
+
+    #[test]
+    fn whisper() {
+        let whisper = Whisper { cells: vec![(0, 2), (0, 3), (1, 3)], gap: 5 };
+        let board = board(9, &[(0, 3, 3)]);
+        assert!(whisper.allows(&board, 8, 0, 2));
+        assert!(!whisper.allows(&board, 7, 0, 2));
+        assert!(!whisper.allows(&self::board(9, &[]), 5, 0, 3)); // nothing is 5 away from 5
+        assert!(whisper.is_satisfied(&self::board(9, &[(0, 2, 1), (0, 3, 6), (1, 3, 1)])));
+        assert!(!whisper.is_satisfied(&self::board(9, &[(0, 2, 1), (0, 3, 6), (1, 3, 2)])));
+    }
+
+    #[test]
+    fn renban() {
+        let renban = Renban { cells: vec![(0, 0), (0, 1), (0, 2)] };
+        let board = board(9, &[(0, 0, 4)]);
+        assert!(renban.allows(&board, 6, 0, 1));
+        assert!(!renban.allows(&board, 7, 0, 1) && !renban.allows(&board, 4, 0, 1));
+        assert!(renban.is_satisfied(&self::board(9, &[(0, 0, 5), (0, 1, 4), (0, 2, 6)])));
+        assert!(!renban.is_satisfied(&self::board(9, &[(0, 0, 4), (0, 1, 6), (0, 2, 7)])));
+    }
+
+    #[test]
+    fn palindrome() {
+        let palindrome = Palindrome { cells: vec![(0, 0), (1, 4), (2, 8)] };
+        let board = board(9, &[(0, 0, 3)]);
+        assert!(palindrome.allows(&board, 3, 2, 8));
+        assert!(!palindrome.allows(&board, 4, 2, 8));
+        assert!(palindrome.allows(&board, 7, 1, 4)); // the middle square mirrors itself
+        // the ends of this one are in the same column, so they can never match
+        let palindrome = Palindrome { cells: vec![(0, 0), (0, 4), (4, 4), (4, 0)] };
+        assert!(!palindrome.allows(&self::board(9, &[]), 3, 0, 0));
+        // these ends only share a diagonal, which is just as much a unit as a row once Sudoku X is on
+        let palindrome = Palindrome { cells: vec![(0, 0), (1, 1), (2, 2), (3, 3), (4, 4)] };
+        let mut board = self::board(9, &[]);
+        assert!(palindrome.allows(&board, 3, 0, 0));
+        board.rules.push(Box::new(AllDifferent { units: diagonal_units(9) }));
+        assert!(!palindrome.allows(&board, 3, 0, 0));
+        assert!(Palindrome { cells: vec![(0, 0), (1, 4), (2, 8)] }.is_satisfied(&self::board(9, &[(0, 0, 3), (1, 4, 5), (2, 8, 3)])));
+        assert!(!Palindrome { cells: vec![(0, 0), (1, 4), (2, 8)] }.is_satisfied(&self::board(9, &[(0, 0, 3), (1, 4, 5), (2, 8, 4)])));
+    }
+
+    #[test]
+    fn parity() {
+        let even = Parity { cell: (0, 0), even: true };
+        let empty = board(9, &[]);
+        assert!(even.allows(&empty, 2, 0, 0) && !even.allows(&empty, 3, 0, 0));
+        assert!(even.allows(&empty, 3, 1, 1));
+        assert!(even.is_satisfied(&board(9, &[(0, 0, 4)])));
+        assert!(!even.is_satisfied(&empty));
+    }
+
+    #[test]
+    fn sum_combinations_lists_every_set() {
+        assert_eq!(sum_combinations(2, 3, 9), vec![0b11]);
+        assert_eq!(sum_combinations(2, 10, 9).len(), 4); // 1+9, 2+8, 3+7 and 4+6
+        assert_eq!(sum_combinations(9, 45, 9), vec![0b1_1111_1111]);
+        assert!(sum_combinations(3, 5, 9).is_empty());
+        assert!(sum_combinations(2, 18, 9).is_empty());
+        assert!(sum_is_possible(3, 6, 9) && sum_is_possible(3, 24, 9));
+        assert!(!sum_is_possible(3, 5, 9) && !sum_is_possible(3, 25, 9) && !sum_is_possible(10, 55, 9));
+    }
+
+    #[test]
+    fn cage() {
+        let cage = Cage::new(vec![(0, 0), (0, 1), (1, 0)], 7, 9);
+        let board = board(9, &[(0, 0, 1)]);
+        assert!(cage.allows(&board, 2, 0, 1)); // 1 + 2 + 4
+        assert!(!cage.allows(&board, 1, 1, 0)); // a repeat
+        assert!(!cage.allows(&board, 7, 0, 1)); // already past the sum
+        assert!(cage.allows(&board, 9, 5, 5)); // outside the cage
+        let full = self::board(9, &[(0, 0, 1), (0, 1, 2), (1, 0, 4)]);
+        assert!(cage.is_satisfied(&full));
+        assert!(!cage.is_satisfied(&self::board(9, &[(0, 0, 1), (0, 1, 2), (1, 0, 5)])));
+    }
+
+    #[test]
+    fn big_cages_use_bounds() {
+        // choosing 20 of 49 values has far too many sets to list, so the cage works from the sums it could still make
+        let cells: Vec<(usize, usize)> = (0..20).map(|c| (0, c)).collect();
+        let cage = Cage::new(cells, 230, 49);
+        assert!(cage.combinations.is_none());
+        let board = board(49, &[(0, 0, 1)]);
+        assert!(cage.allows(&board, 2, 0, 1));
+        assert!(!cage.allows(&board, 49, 0, 1)); // the other 18 squares add up to at least 2 + ... + 19 = 189, more than the 180 left
+        assert!(!cage.allows(&board, 1, 0, 1));
+    }
+}
@@ -1,6 +1,12 @@
 use rand::seq::SliceRandom;
+use rand::rngs::ThreadRng;
 use rand::thread_rng;
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::rules::Rule;
+
 pub trait CheckablySquare {
     fn is_square(&self) -> bool;
     fn root(&self) -> i32;
@@ -26,23 +32,123 @@ impl CheckablySquare for i32 {
     }
 }
 
+// With the `serde` feature a board is written with its lookup tables, but read back from just its values and
+// regions (see BoardFields), and without any extra rules
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "BoardFields"))]
 pub struct SudokuBoard {
     pub values: Vec<Vec<Option<i32>>>,
     pub val_in_row: Vec<Vec<bool>>,
     pub val_in_col: Vec<Vec<bool>>,
     pub val_in_box: Vec<Vec<bool>>,
+    pub regions: Vec<Vec<usize>>, // which bold box (or, for jigsaw puzzles, irregular region) each square is in
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub rules: Vec<Box<dyn Rule>>, // extra rules of variant puzzles, checked along with the tables above
+}
+
+// A board as it is written out. The lookup tables are worked out again from the values, so a file can't leave them
+// out of step, and a file whose tables say something else is rejected.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct BoardFields {
+    values: Vec<Vec<Option<i32>>>,
+    val_in_row: Vec<Vec<bool>>,
+    val_in_col: Vec<Vec<bool>>,
+    val_in_box: Vec<Vec<bool>>,
+    regions: Vec<Vec<usize>>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<BoardFields> for SudokuBoard {
+    type Error = String;
+
+    fn try_from(fields: BoardFields) -> Result<SudokuBoard, String> {
+        let side_length = fields.values.len() as i32;
+        if fields.values.iter().any(|row| row.len() != side_length as usize) || !regions_are_valid(&fields.regions, side_length) {
+            return Err(format!("A board needs {} rows of {} values, and regions of {} squares", side_length, side_length, side_length));
+        }
+        let mut board = new_board(side_length, fields.regions);
+        for (row, values) in fields.values.iter().enumerate() {
+            for (col, value) in values.iter().enumerate() {
+                if let Some(value) = *value {
+                    if value < 1 || value > side_length || !update_board(&mut board, value, row, col) {
+                        return Err(format!("The value {} in row {} column {} can't go there", value, row + 1, col + 1));
+                    }
+                }
+            }
+        }
+        if (board.val_in_row.as_slice(), board.val_in_col.as_slice(), board.val_in_box.as_slice())
+            != (fields.val_in_row.as_slice(), fields.val_in_col.as_slice(), fields.val_in_box.as_slice()) {
+            return Err("The board's lookup tables don't match its values".to_string());
+        }
+        Ok(board)
+    }
+}
+
+// Every way of splitting a side_length x side_length board into boxes, as (rows, cols) of each box. Boxes are at least
+// 2 rows tall and never taller than they are wide, and the most square shape comes first (so 12 gives 3x4, then 2x6).
+pub fn box_shapes(side_length: i32) -> Vec<(i32, i32)> {
+    let mut shapes: Vec<(i32, i32)> = Vec::new();
+    for rows in (2..=side_length).rev() {
+        if side_length % rows == 0 && rows * rows <= side_length {
+            shapes.push((rows, side_length / rows));
+        }
+    }
+    shapes
+}
+
+// The usual box shape for a board size, or None if it can't be split into boxes (like a prime side length)
+pub fn default_box_shape(side_length: i32) -> Option<(i32, i32)> {
+    box_shapes(side_length).first().copied()
+}
+
+// Numbers the boxes of the given shape left to right and then top to bottom, giving the region map of a regular sudoku
+pub fn box_regions(side_length: i32, box_shape: (i32, i32)) -> Vec<Vec<usize>> {
+    let (box_rows, box_cols) = box_shape;
+    // there are box_rows boxes across, as each is side_length / box_rows wide
+    (0..side_length).map(|row| (0..side_length).map(|col| ((box_rows * (row / box_rows)) + (col / box_cols)) as usize).collect()).collect()
+}
+
+// Checks that a region map covers the board and splits it into side_length regions of exactly side_length squares each
+pub fn regions_are_valid(regions: &[Vec<usize>], side_length: i32) -> bool {
+    let mut sizes = vec![0; side_length as usize];
+    if regions.len() != side_length as usize {
+        return false;
+    }
+    for row in regions {
+        if row.len() != side_length as usize {
+            return false;
+        }
+        for region in row {
+            if *region >= side_length as usize {
+                return false;
+            }
+            sizes[*region] += 1;
+        }
+    }
+    sizes.iter().all(|size| *size == side_length)
+}
+
+// Creates an empty board with nothing placed in any row, column, or region
+pub fn new_board(side_length: i32, regions: Vec<Vec<usize>>) -> SudokuBoard {
+    let empty_values: Vec<Vec<Option<i32>>> = vec![vec![None; side_length as usize]; side_length as usize];
+    let row_vals: Vec<Vec<bool>> = vec![vec![false; side_length as usize]; side_length as usize];
+    let col_vals: Vec<Vec<bool>> = vec![vec![false; side_length as usize]; side_length as usize];
+    let box_vals: Vec<Vec<bool>> = vec![vec![false; side_length as usize]; side_length as usize];
+
+    SudokuBoard {values: empty_values, val_in_row: row_vals, val_in_col: col_vals, val_in_box: box_vals, regions, rules: Vec::new()}
 }
 
-// Returns the number of the bold box associated with the given row and column coordinates
-pub fn get_box_num(row: usize, col: usize, side_length: i32) -> usize {
-    let bold_length = side_length.root();
-    return ((bold_length * (row as i32 / bold_length)) + (col as i32 / bold_length)) as usize;
+// Returns the number of the bold box (or jigsaw region) associated with the given row and column coordinates
+pub fn get_box_num(board: &SudokuBoard, row: usize, col: usize) -> usize {
+    board.regions[row][col]
 }
 
-// Places a known value into the board and adjusts the possible values for everything else in its row, column, and bold square (the sqrt(n) x sqrt(n) square which this value is within)
-pub fn update_board(board: &mut SudokuBoard, value: i32, row: usize, col: usize, side_length: i32) -> bool { // returns whether or not value added is possible
-    let box_num: usize = get_box_num(row, col, side_length);
-    if board.val_in_row[(value - 1) as usize][row] || board.val_in_col[(value - 1) as usize][col] || board.val_in_box[(value - 1) as usize][box_num] {
+// Places a known value into the board and adjusts the possible values for everything else in its row, column, and region (the bold box or jigsaw piece which this value is within)
+pub fn update_board(board: &mut SudokuBoard, value: i32, row: usize, col: usize) -> bool { // returns whether or not value added is possible
+    let box_num: usize = get_box_num(board, row, col);
+    if board.val_in_row[(value - 1) as usize][row] || board.val_in_col[(value - 1) as usize][col] || board.val_in_box[(value - 1) as usize][box_num]
+        || !board.rules.iter().all(|rule| rule.allows(board, value, row, col)) {
         return false;
     } else {
         board.val_in_row[(value - 1) as usize][row] = true;
@@ -54,8 +160,8 @@ pub fn update_board(board: &mut SudokuBoard, value: i32, row: usize, col: usize,
 }
 
 // Removes value placed into the board as a guess
-pub fn remove_val(board: &mut SudokuBoard, value: i32, row: usize, col: usize, side_length: i32) -> bool { // returns if it was successfully removed (right now it is always true)
-    let box_num: usize = get_box_num(row, col, side_length);
+pub fn remove_val(board: &mut SudokuBoard, value: i32, row: usize, col: usize) -> bool { // returns if it was successfully removed (right now it is always true)
+    let box_num: usize = get_box_num(board, row, col);
     board.val_in_row[(value - 1) as usize][row] = false;
     board.val_in_col[(value - 1) as usize][col] = false;
     board.val_in_box[(value - 1) as usize][box_num] = false;
@@ -63,7 +169,7 @@ pub fn remove_val(board: &mut SudokuBoard, value: i32, row: usize, col: usize, s
     return true;
 }
 
-// Checks if every row, col, and bold box has every number 1 to side_length inclusive
+// Checks if every row, col, and bold box has every number 1 to side_length inclusive, and that the board keeps to its extra rules
 pub fn is_board_solved(board: &SudokuBoard, side_length: i32) -> i32 { // returns integer code, 1 is solved, 0 means more work required, -1 means failure
     let mut row_check = vec![vec![false; side_length as usize]; side_length as usize];
     let mut col_check = vec![vec![false; side_length as usize]; side_length as usize];
@@ -78,7 +184,7 @@ pub fn is_board_solved(board: &SudokuBoard, side_length: i32) -> i32 { // return
                 let val = entry.unwrap();
                 row_check[row as usize][(val - 1) as usize] = true;
                 col_check[col as usize][(val - 1) as usize] = true;
-                let box_num: usize = get_box_num(row as usize, col as usize, side_length);
+                let box_num: usize = get_box_num(board, row as usize, col as usize);
                 box_check[box_num][(val - 1) as usize] = true;
             }
         }
@@ -90,6 +196,9 @@ pub fn is_board_solved(board: &SudokuBoard, side_length: i32) -> i32 { // return
             }
         }
     }
+    if !board.rules.iter().all(|rule| rule.is_satisfied(board)) {
+        return -1; // puzzle is filled in, but breaks one of the extra rules
+    }
     return 1; // if it makes it to this point without returning, that means all criteria are satisfied
 }
 
@@ -97,193 +206,266 @@ pub fn is_board_solved(board: &SudokuBoard, side_length: i32) -> i32 { // return
 pub fn possible_vals(board: &SudokuBoard, row: usize, col: usize, box_num: usize, side_length: i32) -> Vec<i32> {
     let mut possibilities: Vec<i32> = Vec::new();
     for v in 0..side_length {
-        if !board.val_in_row[v as usize][row] && !board.val_in_col[v as usize][col] && !board.val_in_box[v as usize][box_num] {
+        if !board.val_in_row[v as usize][row] && !board.val_in_col[v as usize][col] && !board.val_in_box[v as usize][box_num]
+            && board.rules.iter().all(|rule| rule.allows(board, v + 1, row, col)) {
             possibilities.push(v + 1)
         }
     }
     return possibilities;
 }
 
-// Attempts to solve the board by guessing possible values, checking if it produces a valid solution, recursively if necessary, then guessing again if not.
-// Once all possible guesses are exhausted, admits defeat and returns false.
-pub fn solve_board(board: &mut SudokuBoard, side_length: i32) -> bool {
-    /*for row in 0..side_length {
-        for col in 0..side_length {
-            if board.values[row as usize][col as usize] != None {
-                print!("| {} |", board.values[row as usize][col as usize].unwrap());
-            } else {
-                print!("|   |");
-            }
+// The values each square could still have as bits (value 1 is bit 0), with 0 for the squares already filled in
+pub fn candidate_masks(board: &SudokuBoard, side_length: i32) -> Vec<Vec<u64>> {
+    let n = side_length as usize;
+    (0..n).map(|r| (0..n).map(|c| match board.values[r][c] {
+        Some(_) => 0,
+        None => value_mask(&possible_vals(board, r, c, get_box_num(board, r, c), side_length)),
+    }).collect()).collect()
+}
+
+fn value_mask(values: &[i32]) -> u64 {
+    values.iter().fold(0, |mask, v| mask | 1 << (v - 1))
+}
+
+// Every row, then every column, then every region, then the groups of side_length squares the extra rules need to be
+// all different. Each of them has to hold every value exactly once.
+pub fn full_units(board: &SudokuBoard, side_length: i32) -> Vec<Vec<(usize, usize)>> {
+    let n = side_length as usize;
+    let mut units: Vec<Vec<(usize, usize)>> = Vec::new();
+    units.extend((0..n).map(|r| (0..n).map(|c| (r, c)).collect()));
+    units.extend((0..n).map(|c| (0..n).map(|r| (r, c)).collect()));
+    let mut regions = vec![Vec::new(); n];
+    for r in 0..n {
+        for c in 0..n {
+            regions[board.regions[r][c]].push((r, c));
         }
-        println!();
-    }*/
-    //println!("solve board called");
-    let initial_solve_result = is_board_solved(board, side_length);
-    if initial_solve_result == 1 {
-        return true;
-    } else if initial_solve_result == -1 {
+    }
+    units.extend(regions);
+    units.extend(board.rules.iter().flat_map(|rule| rule.units()).filter(|unit| unit.len() == n));
+    units
+}
+
+// Places a value and narrows down the values of the squares it affects: the rest of its row, column and region lose
+// that value, and the squares the extra rules link to it are checked against the rules again. Returns false, leaving
+// the board as it was, if the value can't go there or leaves one of those squares with nothing it could be.
+pub fn place(board: &mut SudokuBoard, candidates: &mut [Vec<u64>], units: &[Vec<(usize, usize)>], value: i32, row: usize, col: usize) -> bool {
+    if !update_board(board, value, row, col) {
         return false;
     }
+    let n = board.values.len();
+    let bit = 1u64 << (value - 1);
+    candidates[row][col] = 0;
+    let peers = units[row].iter().chain(&units[n + col]).chain(&units[2 * n + board.regions[row][col]]);
+    for &(r, c) in peers.clone() {
+        candidates[r][c] &= !bit;
+    }
+    let linked: Vec<(usize, usize)> = board.rules.iter().flat_map(|rule| rule.linked_squares(n, row, col)).collect();
+    for &(r, c) in &linked {
+        if board.values[r][c].is_none() {
+            candidates[r][c] &= value_mask(&possible_vals(board, r, c, get_box_num(board, r, c), n as i32));
+        }
+    }
+    if peers.chain(&linked).any(|&(r, c)| board.values[r][c].is_none() && candidates[r][c] == 0) {
+        remove_val(board, value, row, col);
+        return false;
+    }
+    true
+}
 
-    let bold_length = side_length.root();
-
-    // first check if any squares only have one possible value, and fill it in if they do
-    for r in 0..side_length {
-        for c in 0..side_length {
-            if board.values[r as usize][c as usize] == None {
-                let box_num: usize = get_box_num(r as usize, c as usize, side_length);
-                let vs = possible_vals(board, r as usize, c as usize, box_num, side_length);
-                let len = vs.len();
-                if len == 0 {
-                    return false; // if the number of possible values for an empty square is ever 0, the board cannot be solved
-                } else if len == 1 {
-                    if !update_board(board, vs[0], r as usize, c as usize, side_length) {
-                        remove_val(board, vs[0], r as usize, c as usize, side_length);
-                        return false; // if the only possible value is not valid, then the board cannot be solved
-                    } else {
-                        if !solve_board(board, side_length) {
-                            remove_val(board, vs[0], r as usize, c as usize, side_length);
-                            return false;
-                        } else {
-                            return true;
-                        }
-                    }
+// What the candidates say has to happen next
+pub enum Forced {
+    Value(i32, usize, usize), // a square with only one value left, or a value with only one square left in a unit
+    Guess, // nothing is forced
+    Stuck, // a square or a unit has run out of options, so the board can't be solved from here
+}
+
+pub fn find_forced(board: &SudokuBoard, candidates: &[Vec<u64>], units: &[Vec<(usize, usize)>]) -> Forced {
+    let n = board.values.len();
+    for (r, row) in candidates.iter().enumerate() {
+        for (c, &mask) in row.iter().enumerate() {
+            if board.values[r][c].is_none() {
+                match mask.count_ones() {
+                    0 => return Forced::Stuck,
+                    1 => return Forced::Value(mask.trailing_zeros() as i32 + 1, r, c),
+                    _ => {}
                 }
             }
         }
     }
-
-    // second check if nothing else in the same row, column, or box could have a possible value for the each square
-    for r in 0..side_length {
-        for c in 0..side_length {
-            if board.values[r as usize][c as usize] == None {
-                let box_num: usize = get_box_num(r as usize, c as usize, side_length);
-                let vs = possible_vals(board, r as usize, c as usize, box_num, side_length);
-                for v in vs {
-                    let mut rest_of_row_could_have = false;
-                    let mut rest_of_col_could_have = false;
-                    let mut rest_of_box_could_have = false;
-                    for i in 0..side_length {
-                        if i != c && !rest_of_row_could_have {
-                            let row_test = board.values[r as usize][i as usize];
-                            if row_test == None {
-                                if !board.val_in_col[(v - 1) as usize][i as usize] && !board.val_in_box[(v - 1) as usize][get_box_num(r as usize, i as usize, side_length)] {
-                                    // we can assume that !board.val_in_row[(v - 1) as usize][r as usize] will return true because otherwise v would not have been a possible value for this square (which is in the same row)
-                                    rest_of_row_could_have = true;
-                                }
-                            } else if row_test == Some(v) {
-                                rest_of_row_could_have = true;
-                            }
-                        }
-                        if i != r && !rest_of_col_could_have {
-                            let col_test = board.values[i as usize][c as usize];
-                            if col_test == None {
-                                if !board.val_in_row[(v - 1) as usize][i as usize] && !board.val_in_box[(v - 1) as usize][get_box_num(i as usize, c as usize, side_length)] {
-                                    // we can assume that !board.val_in_col[(v - 1) as usize][c as usize] will return true because otherwise v would not have been a possible value for this square (which is in the same col)
-                                    rest_of_col_could_have = true;
-                                }
-                            } else if col_test == Some(v) {
-                                rest_of_col_could_have = true;
-                            }
-                        }
-                    }
-                    if !rest_of_row_could_have || !rest_of_col_could_have { // nothing else in either the row or column could be a possible value for this one, so this square must be that value
-                        if !update_board(board, v, r as usize, c as usize, side_length) {
-                            remove_val(board, v, r as usize, c as usize, side_length);
-                            return false; // this square must be a value that results in an invalid board
-                        } else {
-                            if !solve_board(board, side_length) {
-                                remove_val(board, v, r as usize, c as usize, side_length);
-                                return false;
-                            } else {
-                                return true;
-                            }
-                        }
-                    } else {
-                        let box_left_col = bold_length * (c / bold_length);
-                        let box_right_col = box_left_col + bold_length - 1;
-                        let box_top_row = bold_length * (r / bold_length);
-                        let box_bot_row = box_top_row + bold_length - 1;
-                        for row in box_top_row..=box_bot_row {
-                            for col in box_left_col..=box_right_col {
-                                if !rest_of_box_could_have && (row != r || col != c) { // equivalent to !(r == row && c == col), but in theory because we just check the same row and column, this could be an AND
-                                    let box_test = board.values[row as usize][col as usize];
-                                    if box_test == None {
-                                        if !board.val_in_row[(v - 1) as usize][row as usize] && !board.val_in_col[(v - 1) as usize][col as usize] {
-                                            // we can assume that !board.val_in_box[(v - 1) as usize][c as usize] will return true because otherwise v would not have been a possible value for this square (which is in the same box)
-                                            rest_of_box_could_have = true;
-                                        }
-                                    } else if box_test == Some(v) {
-                                        rest_of_box_could_have = true;
-                                    }
-                                }
-                            }
-                        }
-                        if !rest_of_box_could_have {
-                            if !update_board(board, v, r as usize, c as usize, side_length) {
-                                remove_val(board, v, r as usize, c as usize, side_length);
-                                return false; // this square must be a value that results in an invalid board
-                            } else {
-                                if !solve_board(board, side_length) {
-                                    remove_val(board, v, r as usize, c as usize, side_length);
-                                    return false;
-                                } else {
-                                    return true;
-                                }
-                            }
-                        }
-                    }
+    let every_value = (1u64 << n) - 1;
+    for unit in units {
+        // the values placed in the unit, and the values that one or more of its empty squares could have
+        let (mut placed, mut once, mut twice) = (0u64, 0u64, 0u64);
+        for &(r, c) in unit {
+            match board.values[r][c] {
+                Some(v) => placed |= 1 << (v - 1),
+                None => {
+                    twice |= once & candidates[r][c];
+                    once |= candidates[r][c];
                 }
             }
         }
+        if placed | once != every_value {
+            return Forced::Stuck;
+        }
+        let single = once & !twice & !placed;
+        if single != 0 {
+            let bit = single & single.wrapping_neg();
+            let &(r, c) = unit.iter().find(|&&(r, c)| board.values[r][c].is_none() && candidates[r][c] & bit != 0).unwrap();
+            return Forced::Value(bit.trailing_zeros() as i32 + 1, r, c);
+        }
     }
-    // third guess possible values for each square and see if they could work (guesses on square with the fewest possible values first)
-    let mut rng = thread_rng();
-    let mut min_len = side_length + 1;
-    let mut min_vs: Vec<i32> = vec![0];
-    let mut min_info: (i32, i32) = (0,0); // row, col
-    for r in 0..side_length {
-        for c in 0..side_length {
-            if board.values[r as usize][c as usize] == None {
-                let box_num: usize = get_box_num(r as usize, c as usize, side_length);
-                let mut vs = possible_vals(board, r as usize, c as usize, box_num, side_length);
-                let len = vs.len();
-                if len == 2 { // the lowest possible number of values to guess from is 2, so if we find this we immediately do the guess
-                    vs.shuffle(&mut rng);
-                    for guess_v in vs {
-                        // place the value in the board, see if it could work
-                        if !update_board(board, guess_v, r as usize, c as usize, side_length) {
-                            return false; // guess value was not valid, this should never happen
-                        } else {
-                            if solve_board(board, side_length) {
-                               return true;
-                            } else {
-                               remove_val(board, guess_v, r as usize, c as usize, side_length);
-                            }
-                        }
-                   }
-                   return false; // if it deemed that none of the possible values for any given square could lead to a solvable board, then the board is unsolvable
-                } else if (len as i32) < min_len {
-                    min_len = len as i32;
-                    min_vs = vs.clone();
-                    min_info = (r, c);
+    Forced::Guess
+}
+
+// Fills in everything that is forced, then guesses at the square with the fewest values left, trying them in a
+// random order. Returns whether the board got solved, leaving it as it was if not.
+fn search(board: &mut SudokuBoard, mut candidates: Vec<Vec<u64>>, units: &[Vec<(usize, usize)>], rng: &mut ThreadRng) -> bool {
+    let n = board.values.len();
+    let mut placed: Vec<(i32, usize, usize)> = Vec::new();
+    let solved = loop {
+        match find_forced(board, &candidates, units) {
+            Forced::Value(v, r, c) => {
+                if !place(board, &mut candidates, units, v, r, c) {
+                    break false;
                 }
+                placed.push((v, r, c));
+            }
+            Forced::Stuck => break false,
+            Forced::Guess => {
+                let empty = (0..n).flat_map(|r| (0..n).map(move |c| (r, c))).filter(|&(r, c)| board.values[r][c].is_none());
+                let (r, c) = match empty.min_by_key(|&(r, c)| candidates[r][c].count_ones()) {
+                    Some(cell) => cell,
+                    None => break is_board_solved(board, n as i32) == 1, // every square is filled in
+                };
+                let mut guesses: Vec<i32> = (1..=n as i32).filter(|v| candidates[r][c] & 1 << (v - 1) != 0).collect();
+                guesses.shuffle(rng);
+                break guesses.into_iter().any(|v| {
+                    let mut after = candidates.clone();
+                    if !place(board, &mut after, units, v, r, c) {
+                        return false;
+                    }
+                    search(board, after, units, rng) || !remove_val(board, v, r, c)
+                });
             }
         }
+    };
+    if !solved {
+        for &(v, r, c) in placed.iter().rev() {
+            remove_val(board, v, r, c);
+        }
     }
-    let (r, c) = min_info;
-    min_vs.shuffle(&mut rng);
-    for guess_v in min_vs {
-        // place the value in the board, see if it could work
-        if !update_board(board, guess_v, r as usize, c as usize, side_length) {
-            return false; // guess value was not valid, this should never happen
-        } else {
-            if solve_board(board, side_length) {
-                return true;
-            } else {
-                remove_val(board, guess_v, r as usize, c as usize, side_length);
-            }
+    solved
+}
+
+// Solves the board in place, returning whether it could be solved (and leaving it as it was if not). Every empty
+// square keeps track of the values it could still have as bits, narrowed down as values are placed, so finding the
+// square with the fewest values left and the singles is cheap enough for the millions of guesses a hard variant
+// puzzle can take. Placing a value also narrows down the squares the extra rules link to it, so those rules prune
+// the search as it goes rather than only once a guess has been made.
+pub fn solve_board(board: &mut SudokuBoard, side_length: i32) -> bool {
+    match is_board_solved(board, side_length) {
+        1 => return true,
+        -1 => return false,
+        _ => {}
+    }
+    let units = full_units(board, side_length);
+    let candidates = candidate_masks(board, side_length);
+    search(board, candidates, &units, &mut thread_rng())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::NonConsecutive;
+
+    fn board(side_length: i32) -> SudokuBoard {
+        new_board(side_length, box_regions(side_length, default_box_shape(side_length).unwrap()))
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_round_trip() {
+        let mut board = board(9);
+        assert!(update_board(&mut board, 5, 0, 0));
+        assert!(update_board(&mut board, 3, 4, 7));
+        let json = serde_json::to_string(&board).unwrap();
+        let read: SudokuBoard = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.values, board.values);
+        assert_eq!((read.val_in_row, read.val_in_col, read.val_in_box), (board.val_in_row, board.val_in_col, board.val_in_box));
+
+        // tables that say 5 isn't in row 1, or values that repeat, are refused
+        let stale = json.replacen("true", "false", 1);
+        assert!(serde_json::from_str::<SudokuBoard>(&stale).is_err());
+        let repeated = json.replacen("null", "5", 1);
+        assert!(serde_json::from_str::<SudokuBoard>(&repeated).is_err());
+    }
+
+    #[test]
+    fn solves_and_keeps_givens() {
+        let mut board = board(16);
+        assert!(update_board(&mut board, 7, 0, 0));
+        assert!(update_board(&mut board, 16, 5, 9));
+        assert!(solve_board(&mut board, 16));
+        assert_eq!(is_board_solved(&board, 16), 1);
+        assert_eq!((board.values[0][0], board.values[5][9]), (Some(7), Some(16)));
+    }
+
+    #[test]
+    fn rectangular_boxes() {
+        assert_eq!(box_shapes(12), vec![(3, 4), (2, 6)]);
+        assert_eq!(box_shapes(6), vec![(2, 3)]);
+        assert_eq!(box_shapes(9), vec![(3, 3)]);
+        assert!(box_shapes(7).is_empty());
+
+        let regions = box_regions(6, (2, 3));
+        assert_eq!(regions[0], vec![0, 0, 0, 1, 1, 1]);
+        assert_eq!(regions[5], vec![4, 4, 4, 5, 5, 5]);
+        assert!(regions_are_valid(&regions, 6));
+
+        // the less usual 2x6 boxes of a 12x12 board
+        let mut board = new_board(12, box_regions(12, (2, 6)));
+        assert!(solve_board(&mut board, 12));
+        assert_eq!(is_board_solved(&board, 12), 1);
+        for (r, c) in [(0, 0), (1, 5), (0, 6), (11, 11)] {
+            assert_eq!(board.regions[r][c], 2 * (r / 2) + c / 6);
         }
     }
-    return false; // if it deemed that none of the possible values for any given square could lead to a solvable board, then the board is unsolvable
-}
\ No newline at end of file
+
+    #[test]
+    fn jigsaw_regions() {
+        // each row of a 4x4 is a region, so the columns are the only other thing keeping values apart
+        let rows: Vec<Vec<usize>> = (0..4).map(|r| vec![r; 4]).collect();
+        assert!(regions_are_valid(&rows, 4));
+        let mut board = new_board(4, rows.clone());
+        assert!(update_board(&mut board, 1, 0, 0));
+        assert!(!update_board(&mut board, 1, 0, 3)); // same region
+        assert!(update_board(&mut board, 1, 1, 1)); // a regular 2x2 box would refuse this
+        assert!(solve_board(&mut board, 4));
+        assert_eq!(is_board_solved(&board, 4), 1);
+
+        let mut uneven = rows.clone();
+        uneven[0][0] = 1; // region 0 has three squares and region 1 has five
+        assert!(!regions_are_valid(&uneven, 4));
+        let mut missing = rows.clone();
+        missing[3][3] = 4; // there is no fifth region on a 4x4
+        assert!(!regions_are_valid(&missing, 4));
+        assert!(!regions_are_valid(&rows[..3], 4));
+        let short: Vec<Vec<usize>> = rows.iter().map(|row| row[..3].to_vec()).collect();
+        assert!(!regions_are_valid(&short, 4));
+    }
+
+    #[test]
+    fn non_consecutive() {
+        let mut board = board(9);
+        board.rules.push(Box::new(NonConsecutive));
+        assert!(solve_board(&mut board, 9));
+        assert_eq!(is_board_solved(&board, 9), 1);
+
+        // the four squares of a 2x2 box can't hold 1 to 4 without two consecutive values touching
+        let mut board = self::board(4);
+        board.rules.push(Box::new(NonConsecutive));
+        assert!(!solve_board(&mut board, 4));
+        assert!(board.values.iter().flatten().all(|value| value.is_none()));
+    }
+}
@@ -0,0 +1,172 @@
+// The symbols used to show the values 1 to side_length, so that any size of puzzle can be written down
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+const ALPHANUMERIC: &str = "123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+const LETTERS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SymbolSet {
+    #[default]
+    Alphanumeric, // 1-9, then A-Z, then a-z, so up to 61 values with one character each
+    Letters,      // A-Z, up to 26 values
+    Numbers,      // 1, 2, ... 10, 11, ..., as many values as needed, but with more than one character from 10 on
+    Custom(Vec<String>), // one symbol per value, in order
+}
+
+impl SymbolSet {
+    // Letters and digits while there are enough of them, numbers for larger puzzles
+    pub fn default_for(side_length: i32) -> SymbolSet {
+        if SymbolSet::Alphanumeric.supports(side_length) {
+            SymbolSet::Alphanumeric
+        } else {
+            SymbolSet::Numbers
+        }
+    }
+
+    // The largest side length these symbols can show, or None if there is no limit
+    pub fn max_side_length(&self) -> Option<i32> {
+        match self {
+            SymbolSet::Alphanumeric => Some(ALPHANUMERIC.len() as i32),
+            SymbolSet::Letters => Some(LETTERS.len() as i32),
+            SymbolSet::Numbers => None,
+            SymbolSet::Custom(symbols) => Some(symbols.len() as i32),
+        }
+    }
+
+    pub fn supports(&self, side_length: i32) -> bool {
+        self.max_side_length().is_none_or(|max| side_length <= max)
+    }
+
+    // Whether every value of a puzzle of this size is written with exactly one character
+    pub fn is_single_char(&self, side_length: i32) -> bool {
+        match self {
+            SymbolSet::Alphanumeric | SymbolSet::Letters => true,
+            SymbolSet::Numbers => side_length <= 9,
+            SymbolSet::Custom(symbols) => symbols.iter().all(|s| s.chars().count() == 1),
+        }
+    }
+
+    pub fn symbol(&self, value: i32) -> String {
+        let i = (value - 1) as usize;
+        match self {
+            SymbolSet::Alphanumeric => ALPHANUMERIC[i..=i].to_string(),
+            SymbolSet::Letters => LETTERS[i..=i].to_string(),
+            SymbolSet::Numbers => value.to_string(),
+            SymbolSet::Custom(symbols) => symbols[i].clone(),
+        }
+    }
+
+    // Reads a symbol back into its value, returning None if it isn't one of the first side_length symbols.
+    // Letters are matched regardless of case as long as that can't be ambiguous.
+    pub fn value(&self, symbol: &str, side_length: i32) -> Option<i32> {
+        let symbol = symbol.trim();
+        let index = match self {
+            SymbolSet::Alphanumeric | SymbolSet::Letters => {
+                let mut chars = symbol.chars();
+                let ch = match (chars.next(), chars.next()) {
+                    (Some(ch), None) => ch,
+                    _ => return None,
+                };
+                if *self == SymbolSet::Letters {
+                    LETTERS.find(ch.to_ascii_uppercase())
+                } else if side_length <= 35 {
+                    ALPHANUMERIC.find(ch.to_ascii_uppercase())
+                } else {
+                    ALPHANUMERIC.find(ch)
+                }
+            }
+            SymbolSet::Numbers => symbol.parse::<usize>().ok().filter(|v| *v >= 1).map(|v| v - 1),
+            SymbolSet::Custom(symbols) => symbols.iter().position(|s| s == symbol)
+                .or_else(|| symbols.iter().position(|s| s.eq_ignore_ascii_case(symbol))),
+        }?;
+        if (index as i32) < side_length {
+            Some(index as i32 + 1)
+        } else {
+            None
+        }
+    }
+
+    // Whether `text` could still become a symbol as more is typed, like the 1 at the start of 12
+    pub fn is_prefix(&self, text: &str, side_length: i32) -> bool {
+        (1..=side_length).any(|v| self.symbol(v).to_lowercase().starts_with(&text.to_lowercase()))
+    }
+
+    // Writes a list of values (like a square's pencil marks), separating them only if a symbol can be longer than a character
+    pub fn format_list(&self, values: &[i32], side_length: i32) -> String {
+        let symbols: Vec<String> = values.iter().map(|v| self.symbol(*v)).collect();
+        symbols.join(if self.is_single_char(side_length) {""} else {" "})
+    }
+
+    // Reads a list written by format_list, skipping anything that isn't a symbol
+    pub fn parse_list(&self, text: &str, side_length: i32) -> Vec<i32> {
+        if self.is_single_char(side_length) {
+            text.chars().filter_map(|ch| self.value(&ch.to_string(), side_length)).collect()
+        } else {
+            text.split(|ch: char| ch.is_whitespace() || ch == ',').filter_map(|s| self.value(s, side_length)).collect()
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            SymbolSet::Alphanumeric => "alphanumeric".to_string(),
+            SymbolSet::Letters => "letters".to_string(),
+            SymbolSet::Numbers => "numbers".to_string(),
+            SymbolSet::Custom(symbols) => format!("custom {}", symbols.join(",")),
+        }
+    }
+
+    // Reads the name written by `name`, custom symbols being given as a comma separated list
+    pub fn from_name(name: &str) -> Option<SymbolSet> {
+        let (kind, rest) = name.trim().split_once(' ').unwrap_or((name.trim(), ""));
+        match kind {
+            "alphanumeric" => Some(SymbolSet::Alphanumeric),
+            "letters" => Some(SymbolSet::Letters),
+            "numbers" => Some(SymbolSet::Numbers),
+            "custom" => {
+                let symbols: Vec<String> = rest.split(',').map(|s| s.trim().to_string()).collect();
+                if symbols.iter().any(|s| s.is_empty()) {
+                    None
+                } else {
+                    Some(SymbolSet::Custom(symbols))
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_round_trip() {
+        let custom = SymbolSet::Custom(["Red", "Green", "Blue", "Gold"].iter().map(|s| s.to_string()).collect());
+        let sets = [(SymbolSet::Alphanumeric, 61), (SymbolSet::Letters, 26), (SymbolSet::Numbers, 100), (custom, 4)];
+        for (symbols, max) in sets {
+            assert_eq!(SymbolSet::from_name(&symbols.name()), Some(symbols.clone()));
+            for side_length in [4, 9, 16, 25, 36, 49, max].into_iter().filter(|n| *n <= max) {
+                for v in 1..=side_length {
+                    assert_eq!(symbols.value(&symbols.symbol(v), side_length), Some(v), "{} of {} in {}", v, side_length, symbols.name());
+                }
+                assert_eq!(symbols.value(&symbols.symbol(1), 0), None); // not one of the first side_length symbols
+                let values: Vec<i32> = (1..=side_length).rev().collect();
+                assert_eq!(symbols.parse_list(&symbols.format_list(&values, side_length), side_length), values);
+            }
+        }
+    }
+
+    #[test]
+    fn case_only_matters_when_it_has_to() {
+        assert_eq!(SymbolSet::Alphanumeric.value("a", 16), Some(10));
+        assert_eq!(SymbolSet::Alphanumeric.value("a", 35), Some(10));
+        assert_eq!(SymbolSet::Alphanumeric.value("a", 36), Some(36)); // from 36 on, a to z are symbols of their own
+        assert_eq!(SymbolSet::Letters.value("c", 9), Some(3));
+        assert_eq!(SymbolSet::Numbers.value("10", 9), None);
+        assert_eq!(SymbolSet::Numbers.value("0", 9), None);
+        assert!(SymbolSet::Numbers.is_prefix("1", 12) && !SymbolSet::Numbers.is_prefix("13", 12));
+    }
+}